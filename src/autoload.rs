@@ -1,85 +1,240 @@
-use anyhow::{Ok, Result};
 use askama::Template;
-use std::{collections::HashMap, path::PathBuf};
+use md5::{Digest, Md5};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use crate::classmap;
+use crate::composer_json;
+use crate::error::{Error, Result};
 use crate::lock;
+use crate::report::{Event, Reporter};
+use crate::version;
 
 #[derive(Template)]
 #[template(path = "autoload.html")]
 struct ComposerAutoload {
-    hash: String,
+    suffix: String,
 }
 
 #[derive(Template)]
-#[template(path = "autoload_real.html")]
+#[template(path = "autoload_real.html", escape = "none")]
 struct ComposerRealTemplate {
-    hash: String,
+    suffix: String,
+    classmap_authoritative: bool,
+    apcu_prefix: Option<String>,
+    include_paths: bool,
+    prepend: bool,
+    platform_check: bool,
 }
 
 #[derive(Template)]
-#[template(path = "autoload_static.html")]
+#[template(path = "autoload_static.html", escape = "none")]
 struct ComposerStaticTemplate {
-    hash: String,
-    files: HashMap<String, String>,
-    psr0: HashMap<String, HashMap<String, HashMap<String, usize>>>,
-    psr4: HashMap<String, Vec<String>>,
-    psr4_prefix: HashMap<String, HashMap<String, usize>>,
-    classmap: HashMap<String, String>
+    suffix: String,
+    files: Vec<(String, String)>,
+    psr0_prefix: PrefixGroups<Vec<String>>,
+    psr4: Vec<(String, Vec<String>)>,
+    psr4_prefix: PrefixGroups<usize>,
+    fallback_psr0: Vec<String>,
+    fallback_psr4: Vec<String>,
+    classmap: BTreeMap<String, String>,
 }
 
 #[derive(Template)]
-#[template(path = "Classloader.html")]
+#[template(path = "autoload_map.html", escape = "none")]
+struct AutoloadMapTemplate {
+    name: &'static str,
+    entries: Vec<(String, String)>,
+}
+
+#[derive(Template)]
+#[template(path = "autoload_include_path.html", escape = "none")]
+struct IncludePathTemplate {
+    paths: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "platform_check.html", escape = "none")]
+struct PlatformCheckTemplate {
+    php: Option<(String, u64, String)>,
+    php_64bit: bool,
+    extensions: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "ClassLoader.html")]
 struct ClassloaderTemplate {}
 
+#[derive(Template)]
+#[template(path = "InstalledVersions.html")]
+struct InstalledVersionsTemplate {}
+
+#[derive(Template)]
+#[template(path = "installed.html", escape = "none")]
+struct InstalledTemplate {
+    root: InstalledRoot,
+    versions: BTreeMap<String, InstalledVersion>,
+    dev_mode: bool,
+}
+
+struct InstalledRoot {
+    name: String,
+    pretty_version: String,
+    version: String,
+    reference: Option<String>,
+    package_type: String,
+    aliases: Vec<String>,
+}
+
+struct InstalledPackage {
+    pretty_version: String,
+    version: String,
+    reference: Option<String>,
+    package_type: String,
+    install_path: Option<String>,
+    aliases: Vec<String>,
+}
+
+/// An entry of installed.php, replaced or provided packages only have the `replaced`/`provided` versions
+#[derive(Default)]
+struct InstalledVersion {
+    installed: Option<InstalledPackage>,
+    dev_requirement: bool,
+    replaced: Vec<String>,
+    provided: Vec<String>,
+}
+
+/// How the autoloader is generated
+#[derive(Clone, Default)]
+pub struct AutoloadOptions {
+    /// Also autoload `autoload-dev` and the packages of `packages-dev`, without one the mode of
+    /// the last install is kept
+    pub dev_mode: Option<bool>,
+    /// How classmap directories are searched for classes
+    pub scan_mode: classmap::ScanMode,
+    /// Fail instead of warning when a class is declared in more than one file
+    pub strict_ambiguous: bool,
+    /// Check that PSR-0 and PSR-4 directories only contain classes where the standard expects them
+    pub strict_psr: bool,
+    /// Convert PSR-0 and PSR-4 directories into the classmap
+    pub optimize: bool,
+    /// Only load classes from the classmap, implies `optimize`
+    pub classmap_authoritative: bool,
+    /// Cache found and missing classes in APCu under this prefix
+    pub apcu_prefix: Option<String>,
+    /// Receives ambiguous classes, PSR violations and files which could not be scanned
+    pub reporter: Reporter,
+}
+
+impl AutoloadOptions {
+    pub fn optimize(&self) -> bool {
+        self.optimize || self.classmap_authoritative
+    }
+}
+
 pub async fn generate_composer_autoload(
     lock: lock::ComposerLock,
+    root: composer_json::ComposerJson,
     vendor_directory: PathBuf,
+    dev_mode: bool,
+    options: AutoloadOptions,
 ) -> Result<()> {
     let composer_directory = vendor_directory.join("composer");
     if !composer_directory.exists() {
         tokio::fs::create_dir_all(&composer_directory)
             .await
-            .expect("Failed to create composer directory");
+            .map_err(Error::io(&composer_directory))?;
     }
 
-    generate_main_autoload(lock.clone(), vendor_directory.clone())
-        .await
-        .expect("Failed to generate vendor/autoload.php file");
+    // Like Composer a configured suffix keeps the autoloader class names stable across lock changes
+    let config = root.config();
+    let suffix = config
+        .autoloader_suffix
+        .clone()
+        .unwrap_or(lock.content_hash.clone());
 
-    generate_composer_real(lock.clone(), vendor_directory.clone())
-        .await
-        .expect("Failed to generate autoload_real.php file");
+    generate_main_autoload(&suffix, vendor_directory.clone()).await?;
 
-    generate_composer_static(lock.clone(), vendor_directory.clone())
-        .await
-        .expect("Failed to generate autoload_static.php file");
+    let include_paths = generate_include_paths(&lock, &root, &vendor_directory, dev_mode).await?;
 
-    generate_composer_classloader(vendor_directory.clone())
-        .await
-        .expect("Failed to generate ClassLoader.php file");
+    let platform_check = generate_platform_check(&lock, &root, &vendor_directory, dev_mode).await?;
+
+    generate_composer_real(
+        &suffix,
+        vendor_directory.clone(),
+        &options,
+        include_paths,
+        config.prepend_autoloader.unwrap_or(true),
+        platform_check,
+    )
+    .await?;
+
+    let strict_psr = options.strict_psr;
+    let strict_ambiguous = options.strict_ambiguous;
+
+    let issues = generate_composer_static(
+        &suffix,
+        lock.clone(),
+        root.clone(),
+        vendor_directory.clone(),
+        dev_mode,
+        options,
+    )
+    .await?;
+
+    generate_composer_classloader(vendor_directory.clone()).await?;
+
+    generate_installed_versions(lock.clone(), root, vendor_directory.clone(), dev_mode).await?;
+
+    // Like Composer the strict checks fail only once all files are written, a partially written
+    // autoloader would reference classes of another suffix
+    if strict_psr && issues.psr_violations > 0 {
+        return Err(Error::PsrViolations(issues.psr_violations));
+    }
+
+    if strict_ambiguous && issues.ambiguous_classes > 0 {
+        return Err(Error::AmbiguousClasses(issues.ambiguous_classes));
+    }
 
     Ok(())
 }
 
-async fn generate_main_autoload(lock: lock::ComposerLock, vendor_directory: PathBuf) -> Result<()> {
+/// Problems found while building the classmap, which only fail in strict mode
+struct ClassmapIssues {
+    psr_violations: usize,
+    ambiguous_classes: usize,
+}
+
+async fn generate_main_autoload(suffix: &str, vendor_directory: PathBuf) -> Result<()> {
     let template = ComposerAutoload {
-        hash: lock.content_hash,
+        suffix: suffix.to_string(),
     };
 
-    tokio::fs::write(
-        vendor_directory.join("autoload.php"),
-        template.render().unwrap(),
-    )
-    .await
-    .expect("Failed to write composer autoload file");
+    let autoload_file = vendor_directory.join("autoload.php");
+    tokio::fs::write(&autoload_file, template.render().unwrap())
+        .await
+        .map_err(Error::io(&autoload_file))?;
 
     Ok(())
 }
 
-async fn generate_composer_real(lock: lock::ComposerLock, vendor_directory: PathBuf) -> Result<()> {
+async fn generate_composer_real(
+    suffix: &str,
+    vendor_directory: PathBuf,
+    options: &AutoloadOptions,
+    include_paths: bool,
+    prepend: bool,
+    platform_check: bool,
+) -> Result<()> {
     let template = ComposerRealTemplate {
-        hash: lock.content_hash,
+        suffix: suffix.to_string(),
+        classmap_authoritative: options.classmap_authoritative,
+        apcu_prefix: options.apcu_prefix.clone(),
+        include_paths,
+        prepend,
+        platform_check,
     };
     let rendered = template.render().unwrap();
 
@@ -87,113 +242,191 @@ async fn generate_composer_real(lock: lock::ComposerLock, vendor_directory: Path
     let classmap_file = composer_directory.join("autoload_real.php");
     tokio::fs::write(&classmap_file, rendered)
         .await
-        .expect("Failed to write composer classmap");
+        .map_err(Error::io(&classmap_file))?;
 
     Ok(())
 }
 
 async fn generate_composer_static(
+    suffix: &str,
     lock: lock::ComposerLock,
+    root: composer_json::ComposerJson,
     vendor_directory: PathBuf,
-) -> Result<()> {
-    let mut files = HashMap::new();
-    let mut psr0: HashMap<String, HashMap<String, HashMap<String, usize>>> = HashMap::new();
-    let mut psr4: HashMap<String, Vec<String>> = HashMap::new();
-    let mut psr4_prefix: HashMap<String, HashMap<String, usize>> = HashMap::new();
-    let mut classmap: HashMap<String, String> = HashMap::new();
-
-    for package in lock.packages {
-        if package.autoload.is_some() {
-            let autoload = package.autoload.unwrap();
-
-            if autoload.files.is_some() {
-                for file in autoload.files.unwrap() {
-                    files.insert(
-                        xxhash_rust::xxh3::xxh3_64(file.as_bytes()).to_string(),
-                        format!("{}/{}", package.name, file),
-                    );
-                }
-            }
+    dev_mode: bool,
+    options: AutoloadOptions,
+) -> Result<ClassmapIssues> {
+    let mut files: Vec<(String, AutoloadPath)> = vec![];
+    let mut psr0_mappings: BTreeMap<String, Vec<(&AutoloadSection, String)>> = BTreeMap::new();
+    let mut psr4_mappings: BTreeMap<String, Vec<(&AutoloadSection, String)>> = BTreeMap::new();
+    let mut classmap = ClassmapBuilder {
+        reporter: options.reporter.clone(),
+        ..Default::default()
+    };
+    let mut psr_violations = 0;
 
-            if autoload.psr0.is_some() {
-                for (namespace, path) in autoload.psr0.unwrap() {
-                    let path_to_directory = format!("{}/{}", package.name, path);
-                    let first_letter = namespace.chars().next().unwrap();
-
-                    if psr0.contains_key(&first_letter.to_string()) {
-                        let first_letter_map = psr0.get_mut(&first_letter.to_string()).unwrap();
-
-                        if first_letter_map.contains_key(&namespace) {
-                            first_letter_map
-                                .get_mut(&namespace)
-                                .unwrap()
-                                .insert(path_to_directory, 1);
-                        } else {
-                            let mut paths = HashMap::new();
-                            paths.insert(path_to_directory, 1);
-                            first_letter_map.insert(namespace.clone(), paths);
-                        }
-                    } else {
-                        let mut prefix = HashMap::new();
-
-                        let mut paths = HashMap::new();
-                        paths.insert(path_to_directory, 1);
-
-                        prefix.insert(namespace.clone(), paths);
-                        psr0.insert(first_letter.to_string(), prefix);
-                    }
-                }
-            }
+    let sections = autoload_sections(&lock, &root, &vendor_directory, dev_mode);
 
-            if autoload.psr4.is_some() {
-                for (namespace, path) in autoload.psr4.unwrap() {
-                    let path_to_directory = format!("{}/{}", package.name, path);
-                    let first_letter = namespace.chars().next().unwrap();
+    // Files are required in dependency order and those of the root package last
+    let mut file_sections = sections
+        .iter()
+        .filter(|section| !section.is_root())
+        .rev()
+        .collect::<Vec<&AutoloadSection>>();
+    file_sections.extend(sections.iter().filter(|section| section.is_root()));
 
-                    if psr4.contains_key(&namespace) {
-                        psr4.get_mut(&namespace).unwrap().push(path_to_directory);
-                    } else {
-                        psr4.insert(namespace.clone(), vec![path_to_directory]);
-                    }
+    for section in file_sections {
+        for file in section.autoload.files.clone().unwrap_or_default() {
+            let file = section.target_path(&file);
+            files.push((section.file_identifier(&file), section.path(&file)));
+        }
+    }
 
-                    if psr4_prefix.contains_key(&first_letter.to_string()) {
-                        psr4_prefix
-                            .get_mut(&first_letter.to_string())
-                            .unwrap()
-                            .insert(namespace.clone(), namespace.len());
-                    } else {
-                        let mut prefix = HashMap::new();
-                        prefix.insert(namespace.clone(), namespace.len());
-                        psr4_prefix.insert(first_letter.to_string(), prefix);
-                    }
-                }
-            }
+    for section in &sections {
+        for (namespace, paths) in section.autoload.psr0.clone().unwrap_or_default() {
+            let mapping = psr0_mappings.entry(namespace).or_default();
+            mapping.extend(paths.into_iter().map(|path| (section, path)));
+        }
+
+        for (namespace, paths) in section.autoload.psr4.clone().unwrap_or_default() {
+            let mapping = psr4_mappings.entry(namespace).or_default();
+            mapping.extend(paths.into_iter().map(|path| (section, path)));
+        }
+    }
+
+    // Like Composer namespaces are sorted in reverse, so `Foo\Bar\` is tried before `Foo\`,
+    // and the directories of a namespace keep the order of the packages
+    let psr_directories = |mappings: &BTreeMap<String, Vec<(&AutoloadSection, String)>>| {
+        mappings
+            .iter()
+            .rev()
+            .map(|(namespace, mapping)| {
+                let directories = mapping
+                    .iter()
+                    .map(|(section, path)| section.path(path))
+                    .collect::<Vec<AutoloadPath>>();
+                (namespace.clone(), directories)
+            })
+            .collect::<Vec<(String, Vec<AutoloadPath>)>>()
+    };
+    let psr0 = psr_directories(&psr0_mappings);
+    let psr4 = psr_directories(&psr4_mappings);
 
-            if autoload.class_map.is_some() {
-                for classmap_path in autoload.class_map.unwrap() {
-                    let mut classmap_pkg = classmap::generate_classmap(
-                        vendor_directory.join(&package.name),
-                        classmap_path,
-                        autoload.exclude_from_class_map.clone().unwrap_or(vec![]),
+    let excludes = classmap::ExcludePatterns::new(
+        &sections
+            .iter()
+            .flat_map(|section| {
+                let patterns = section
+                    .autoload
+                    .exclude_from_class_map
+                    .clone()
+                    .unwrap_or_default();
+                patterns
+                    .into_iter()
+                    .map(|pattern| (section.directory.clone(), section.target_path(&pattern)))
+            })
+            .collect::<Vec<(PathBuf, String)>>(),
+    )?;
+
+    // Like Composer all classmap directories are scanned before the PSR-4 and then PSR-0 ones,
+    // each of them with the root package first and then dependents before their dependencies
+    for section in &sections {
+        for classmap_path in section.autoload.class_map.clone().unwrap_or_default() {
+            let classes = classmap::generate_classmap(
+                section.directory.clone(),
+                section.target_path(&classmap_path),
+                &excludes,
+                options.scan_mode,
+                &options.reporter,
+            )
+            .await?;
+
+            classmap.add(section, classes);
+        }
+    }
+
+    if options.optimize() || options.strict_psr {
+        for (standard, mappings) in [("psr-4", &psr4_mappings), ("psr-0", &psr0_mappings)] {
+            for (namespace, mapping) in mappings.iter().rev() {
+                for (section, path) in mapping {
+                    let (classes, violations) = classmap::generate_psr_classmap(
+                        section.directory.clone(),
+                        path.clone(),
+                        namespace,
+                        standard,
+                        &excludes,
+                        options.scan_mode,
+                        &options.reporter,
                     )
-                    .await
-                    .expect("Failed to generate classmap");
+                    .await?;
 
-                    for (class, file) in classmap_pkg.drain() {
-                        classmap.insert(class, format!("{}/{}", package.name, file));
+                    psr_violations += violations.len();
+                    for violation in violations {
+                        options.reporter.report(Event::PsrViolation(violation));
+                    }
+
+                    if options.optimize() {
+                        classmap.add(section, classes);
                     }
                 }
             }
         }
     }
 
+    let issues = ClassmapIssues {
+        psr_violations,
+        ambiguous_classes: classmap.ambiguous_classes.len(),
+    };
+
+    let mut classmap = classmap.classmap;
+    classmap.insert(
+        "Composer\\InstalledVersions".to_string(),
+        AutoloadPath::new(PathBase::Vendor, "composer/InstalledVersions.php"),
+    );
+
+    generate_autoload_maps(&vendor_directory, &files, &psr0, &psr4, &classmap).await?;
+
+    // An empty namespace is a fallback for all classes
+    let static_directories = |mappings: &[(String, Vec<AutoloadPath>)], fallback: bool| {
+        mappings
+            .iter()
+            .filter(|(namespace, _)| namespace.is_empty() == fallback)
+            .map(|(namespace, directories)| {
+                let directories = directories
+                    .iter()
+                    .map(AutoloadPath::static_code)
+                    .collect::<Vec<String>>();
+                (namespace.clone(), directories)
+            })
+            .collect::<Vec<(String, Vec<String>)>>()
+    };
+    let psr4_static = static_directories(&psr4, false);
+
     let template = ComposerStaticTemplate {
-        hash: lock.content_hash,
-        files: files,
-        psr0: psr0,
-        psr4: psr4,
-        psr4_prefix: psr4_prefix,
-        classmap: classmap,
+        suffix: suffix.to_string(),
+        files: files
+            .iter()
+            .map(|(identifier, file)| (identifier.clone(), file.static_code()))
+            .collect(),
+        psr0_prefix: group_by_first_letter(&static_directories(&psr0, false)),
+        psr4_prefix: group_by_first_letter(
+            &psr4_static
+                .iter()
+                .map(|(namespace, _)| (namespace.clone(), namespace.len()))
+                .collect::<Vec<(String, usize)>>(),
+        ),
+        psr4: psr4_static,
+        fallback_psr0: static_directories(&psr0, true)
+            .into_iter()
+            .flat_map(|(_, directories)| directories)
+            .collect(),
+        fallback_psr4: static_directories(&psr4, true)
+            .into_iter()
+            .flat_map(|(_, directories)| directories)
+            .collect(),
+        classmap: classmap
+            .iter()
+            .map(|(class, file)| (class.clone(), file.static_code()))
+            .collect(),
     };
     let rendered = template.render().unwrap();
 
@@ -201,7 +434,446 @@ async fn generate_composer_static(
     let classmap_file = composer_directory.join("autoload_static.php");
     tokio::fs::write(&classmap_file, rendered)
         .await
-        .expect("Failed to write composer classmap");
+        .map_err(Error::io(&classmap_file))?;
+
+    Ok(issues)
+}
+
+/// Namespaces grouped by their first letter like the prefix maps of the ClassLoader
+type PrefixGroups<T> = Vec<(String, Vec<(String, T)>)>;
+
+/// Groups namespaces by their first letter, keeping their order
+fn group_by_first_letter<T: Clone>(namespaces: &[(String, T)]) -> PrefixGroups<T> {
+    let mut groups: PrefixGroups<T> = vec![];
+
+    for (namespace, value) in namespaces {
+        let first_letter = namespace.chars().next().unwrap().to_string();
+
+        match groups
+            .iter_mut()
+            .find(|(letter, _)| letter == &first_letter)
+        {
+            Some((_, group)) => group.push((namespace.clone(), value.clone())),
+            None => groups.push((first_letter, vec![(namespace.clone(), value.clone())])),
+        }
+    }
+
+    groups
+}
+
+/// Classes of the static classmap, sorted by class, the first file found for a class is the one used
+#[derive(Default)]
+struct ClassmapBuilder {
+    classmap: BTreeMap<String, AutoloadPath>,
+    class_files: HashMap<String, PathBuf>,
+    ambiguous_classes: HashSet<String>,
+    reporter: Reporter,
+}
+
+/// Like Composer duplicates in tests, fixtures, examples and stubs are not ambiguous
+static IGNORED_DUPLICATES: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"(?i)/(test|fixture|example|stub)s?/").unwrap());
+
+impl ClassmapBuilder {
+    fn add(&mut self, section: &AutoloadSection, classes: Vec<(String, String)>) {
+        for (class, file) in classes {
+            let path = section.directory.join(&file);
+
+            match self.class_files.get(&class) {
+                Some(first) if first == &path => {}
+                Some(first) => {
+                    let paths = format!("{} {}", first.display(), path.display());
+                    if IGNORED_DUPLICATES.is_match(&paths.replace('\\', "/")) {
+                        continue;
+                    }
+
+                    self.reporter.report(Event::AmbiguousClass {
+                        class: class.clone(),
+                        first: first.clone(),
+                        other: path.clone(),
+                    });
+                    self.ambiguous_classes.insert(class);
+                }
+                None => {
+                    self.classmap.insert(class.clone(), section.path(&file));
+                    self.class_files.insert(class, path);
+                }
+            }
+        }
+    }
+}
+
+/// Where a path of the autoloader is relative to
+#[derive(Clone, Copy, PartialEq)]
+enum PathBase {
+    Vendor,
+    Root,
+}
+
+/// A normalized path below vendor/ or the project root
+#[derive(Clone)]
+struct AutoloadPath {
+    base: PathBase,
+    path: String,
+}
+
+impl AutoloadPath {
+    /// Normalizes `.` and `..` segments like Composer does
+    fn new(base: PathBase, path: &str) -> Self {
+        let mut segments: Vec<&str> = vec![];
+
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => continue,
+                // Leading `..` segments point above the base directory and are kept
+                ".." if segments.last().is_none_or(|last| *last == "..") => segments.push(segment),
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+
+        AutoloadPath {
+            base,
+            path: format!("/{}", segments.join("/")),
+        }
+    }
+
+    /// PHP expression relative to vendor/composer, as used by autoload_static.php
+    fn static_code(&self) -> String {
+        let base_code = match self.base {
+            PathBase::Vendor => "__DIR__ . '/..'",
+            PathBase::Root => "__DIR__ . '/../..'",
+        };
+
+        format!("{} . '{}'", base_code, php_escape(&self.path))
+    }
+
+    /// PHP expression using the `$vendorDir` and `$baseDir` variables of the other autoload files
+    fn variable_code(&self) -> String {
+        let base_code = match self.base {
+            PathBase::Vendor => "$vendorDir",
+            PathBase::Root => "$baseDir",
+        };
+
+        format!("{} . '{}'", base_code, php_escape(&self.path))
+    }
+}
+
+fn php_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// The autoload configuration of a package or the root package, with the location its paths are relative to
+struct AutoloadSection {
+    name: String,
+    autoload: lock::ComposerAutoload,
+    directory: PathBuf,
+    base: PathBase,
+    prefix: String,
+    target_dir: Option<String>,
+}
+
+impl AutoloadSection {
+    fn path(&self, path: &str) -> AutoloadPath {
+        AutoloadPath::new(self.base, &format!("{}/{}", self.prefix, path))
+    }
+
+    /// Same identifier as Composer, so files are only required once even by several autoloaders
+    fn file_identifier(&self, path: &str) -> String {
+        format!("{:x}", Md5::digest(format!("{}:{}", self.name, path)))
+    }
+
+    /// Files and classmap paths of `target-dir` packages which are not found in the package are
+    /// relative to the target dir, like Composer they are prefixed with it
+    fn target_path(&self, path: &str) -> String {
+        match &self.target_dir {
+            Some(target_dir) if !self.directory.join(path).exists() => {
+                format!("{}/{}", target_dir, path)
+            }
+            _ => path.to_string(),
+        }
+    }
+
+    fn is_root(&self) -> bool {
+        self.base == PathBase::Root
+    }
+}
+
+/// Autoload sections of the root package followed by all packages with dependents before their dependencies, `autoload-dev`
+/// of the root is only used in dev mode
+///
+/// Like Composer the root package comes first, so its PSR directories are searched first and its classes win in the classmap
+fn autoload_sections(
+    lock: &lock::ComposerLock,
+    root: &composer_json::ComposerJson,
+    vendor_directory: &Path,
+    dev_mode: bool,
+) -> Vec<AutoloadSection> {
+    let mut sections = vec![];
+
+    let working_directory = vendor_directory.parent().unwrap();
+    let mut root_autoloads = vec![root.autoload.clone()];
+    if dev_mode {
+        root_autoloads.push(root.autoload_dev.clone());
+    }
+
+    for autoload in root_autoloads.into_iter().flatten() {
+        sections.push(AutoloadSection {
+            name: root.root_name(),
+            autoload,
+            directory: working_directory.to_path_buf(),
+            base: PathBase::Root,
+            prefix: String::new(),
+            target_dir: None,
+        });
+    }
+
+    // Paths of `target-dir` packages are relative to the package directory above the target dir,
+    // so PSR-0 namespaces resolve to the directories of the target dir
+    for package in lock::sort_packages(lock.installable_packages(dev_mode))
+        .into_iter()
+        .rev()
+    {
+        if let Some(autoload) = package.autoload {
+            sections.push(AutoloadSection {
+                name: package.name.clone(),
+                autoload,
+                directory: vendor_directory.join(&package.name),
+                base: PathBase::Vendor,
+                prefix: package.name.clone(),
+                target_dir: package.target_dir.clone(),
+            });
+        }
+    }
+
+    sections
+}
+
+/// autoload_classmap.php, autoload_psr4.php, autoload_namespaces.php and autoload_files.php, which
+/// other tools read to discover the mappings of the autoloader
+async fn generate_autoload_maps(
+    vendor_directory: &Path,
+    files: &[(String, AutoloadPath)],
+    psr0: &[(String, Vec<AutoloadPath>)],
+    psr4: &[(String, Vec<AutoloadPath>)],
+    classmap: &BTreeMap<String, AutoloadPath>,
+) -> Result<()> {
+    let psr_entries = |mappings: &[(String, Vec<AutoloadPath>)]| {
+        mappings
+            .iter()
+            .map(|(namespace, directories)| {
+                let directories = directories
+                    .iter()
+                    .map(AutoloadPath::variable_code)
+                    .collect::<Vec<String>>();
+                (
+                    namespace.clone(),
+                    format!("array({})", directories.join(", ")),
+                )
+            })
+            .collect::<Vec<(String, String)>>()
+    };
+
+    let maps = [
+        (
+            "autoload_classmap.php",
+            classmap
+                .iter()
+                .map(|(class, file)| (class.clone(), file.variable_code()))
+                .collect::<Vec<(String, String)>>(),
+        ),
+        ("autoload_namespaces.php", psr_entries(psr0)),
+        ("autoload_psr4.php", psr_entries(psr4)),
+        (
+            "autoload_files.php",
+            files
+                .iter()
+                .map(|(identifier, file)| (identifier.clone(), file.variable_code()))
+                .collect::<Vec<(String, String)>>(),
+        ),
+    ];
+
+    for (name, entries) in maps {
+        // Composer only writes autoload_files.php when there are files to load
+        if name == "autoload_files.php" && entries.is_empty() {
+            remove_file_if_exists(&vendor_directory.join("composer").join(name)).await?;
+            continue;
+        }
+
+        let template = AutoloadMapTemplate { name, entries };
+        let map_file = vendor_directory.join("composer").join(name);
+
+        tokio::fs::write(&map_file, template.render().unwrap())
+            .await
+            .map_err(Error::io(&map_file))?;
+    }
+
+    Ok(())
+}
+
+/// autoload_include_path.php with the `include-path` of the root package and all packages,
+/// which is only written when there are any, returns whether it was written
+async fn generate_include_paths(
+    lock: &lock::ComposerLock,
+    root: &composer_json::ComposerJson,
+    vendor_directory: &Path,
+    dev_mode: bool,
+) -> Result<bool> {
+    let mut paths = vec![];
+
+    for include_path in root.include_path.iter().flatten() {
+        paths.push(AutoloadPath::new(PathBase::Root, include_path));
+    }
+
+    for package in lock.installable_packages(dev_mode) {
+        for include_path in package.include_path.iter().flatten() {
+            paths.push(AutoloadPath::new(
+                PathBase::Vendor,
+                &format!("{}/{}", package.name, include_path),
+            ));
+        }
+    }
+
+    let include_path_file = vendor_directory
+        .join("composer")
+        .join("autoload_include_path.php");
+
+    if paths.is_empty() {
+        remove_file_if_exists(&include_path_file).await?;
+        return Ok(false);
+    }
+
+    let template = IncludePathTemplate {
+        paths: paths.iter().map(AutoloadPath::variable_code).collect(),
+    };
+
+    tokio::fs::write(&include_path_file, template.render().unwrap())
+        .await
+        .map_err(Error::io(&include_path_file))?;
+
+    Ok(true)
+}
+
+/// platform_check.php, which stops with a clear message when the running PHP does not satisfy the
+/// `php`, `php-64bit` and `ext-*` requirements of the root package and the packages which are not
+/// dev requirements, returns whether it was written
+async fn generate_platform_check(
+    lock: &lock::ComposerLock,
+    root: &composer_json::ComposerJson,
+    vendor_directory: &Path,
+    dev_mode: bool,
+) -> Result<bool> {
+    let platform_check_file = vendor_directory.join("composer").join("platform_check.php");
+
+    // Like Composer any value other than a boolean, such as "php-only", only checks the PHP version
+    let check_extensions = match root.config().platform_check {
+        None | Some(composer_json::PlatformCheck::Enabled(true)) => true,
+        Some(composer_json::PlatformCheck::Mode(mode)) if !mode.is_empty() => false,
+        Some(_) => {
+            remove_file_if_exists(&platform_check_file).await?;
+            return Ok(false);
+        }
+    };
+
+    let packages = lock.installable_packages(dev_mode);
+    let dev_package_names = lock.dev_package_names();
+
+    // Extensions which are replaced or provided by a package, e.g. a polyfill, are not checked
+    let provided_extensions = packages
+        .iter()
+        .flat_map(|package| [&package.replace, &package.provide])
+        .chain([&root.replace, &root.provide])
+        .flatten()
+        .flat_map(|links| links.keys())
+        .filter_map(|target| {
+            let target = target.to_lowercase();
+            target.strip_prefix("ext-").map(str::to_string)
+        })
+        .collect::<HashSet<String>>();
+
+    let requires = packages
+        .iter()
+        .filter(|package| !dev_package_names.contains(&package.name))
+        .map(|package| &package.require)
+        .chain([&root.require])
+        .flatten()
+        .flat_map(|require| require.iter());
+
+    let mut lowest_php = version::Bound::zero();
+    let mut php_64bit = false;
+    let mut extensions = BTreeMap::new();
+
+    for (target, constraint) in requires {
+        let target = target.to_lowercase();
+
+        if target == "php" || target == "php-64bit" {
+            let bound = version::constraint_lower_bound(constraint);
+            if bound.is_higher_than(&lowest_php) {
+                lowest_php = bound;
+            }
+        }
+
+        if target == "php-64bit" {
+            php_64bit = true;
+        }
+
+        let Some(extension) = target.strip_prefix("ext-") else {
+            continue;
+        };
+        if !check_extensions || provided_extensions.contains(extension) {
+            continue;
+        }
+
+        let extension = match extension {
+            "zend-opcache" => "zend opcache",
+            extension => extension,
+        };
+        let name = format!("'{}'", php_escape(extension));
+        // pcntl and readline are only available on the command line
+        let check = match extension {
+            "pcntl" | "readline" => format!(
+                "PHP_SAPI !== 'cli' || extension_loaded({0}) || $missingExtensions[] = {0};",
+                name
+            ),
+            _ => format!("extension_loaded({0}) || $missingExtensions[] = {0};", name),
+        };
+        extensions.insert(name, check);
+    }
+
+    if lowest_php.is_zero() && !php_64bit && extensions.is_empty() {
+        remove_file_if_exists(&platform_check_file).await?;
+        return Ok(false);
+    }
+
+    let php = (!lowest_php.is_zero()).then(|| {
+        (
+            lowest_php.operator().to_string(),
+            lowest_php.php_version_id(),
+            lowest_php.php_version(),
+        )
+    });
+
+    let template = PlatformCheckTemplate {
+        php,
+        php_64bit,
+        extensions: extensions.into_values().collect(),
+    };
+
+    tokio::fs::write(&platform_check_file, template.render().unwrap())
+        .await
+        .map_err(Error::io(&platform_check_file))?;
+
+    Ok(true)
+}
+
+async fn remove_file_if_exists(path: &Path) -> Result<()> {
+    if path.exists() {
+        tokio::fs::remove_file(path)
+            .await
+            .map_err(Error::io(path))?;
+    }
 
     Ok(())
 }
@@ -214,14 +886,306 @@ async fn generate_composer_classloader(vendor_directory: PathBuf) -> Result<()>
     let classmap_file = composer_directory.join("ClassLoader.php");
     tokio::fs::write(&classmap_file, rendered)
         .await
-        .expect("Failed to write composer classmap");
+        .map_err(Error::io(&classmap_file))?;
+
+    Ok(())
+}
+
+/// Writes installed.php and the InstalledVersions class which reads it at runtime
+async fn generate_installed_versions(
+    lock: lock::ComposerLock,
+    root: composer_json::ComposerJson,
+    vendor_directory: PathBuf,
+    dev_mode: bool,
+) -> Result<()> {
+    let working_directory = vendor_directory.parent().unwrap().to_path_buf();
+    let (pretty_version, reference) = composer_json::guess_root_version(&root, &working_directory);
+
+    let root_package = InstalledRoot {
+        name: root.root_name(),
+        version: version::normalize_version(&pretty_version),
+        pretty_version,
+        reference,
+        package_type: root.package_type.clone().unwrap_or("library".to_string()),
+        aliases: vec![],
+    };
+
+    let mut versions: BTreeMap<String, InstalledVersion> = BTreeMap::new();
+    let dev_package_names = lock.dev_package_names();
+    let lock_aliases = lock.aliases.clone().unwrap_or_default();
+    let packages = lock.installable_packages(dev_mode);
+
+    for package in &packages {
+        let is_metapackage = package
+            .package_type
+            .as_ref()
+            .is_some_and(|package_type| package_type == "metapackage");
+
+        let entry = versions.entry(package.name.clone()).or_default();
+        entry.dev_requirement = dev_package_names.contains(&package.name);
+        entry.installed = Some(InstalledPackage {
+            pretty_version: package.version.clone(),
+            version: version::normalize_version(&package.version),
+            reference: package
+                .dist
+                .as_ref()
+                .or(package.source.as_ref())
+                .map(|source| source.reference.clone()),
+            package_type: package
+                .package_type
+                .clone()
+                .unwrap_or("library".to_string()),
+            install_path: match is_metapackage {
+                true => None,
+                false => Some(format!("../{}", package.install_path())),
+            },
+            aliases: package.aliases(&lock_aliases),
+        });
+    }
+
+    let root_entry = versions.entry(root_package.name.clone()).or_default();
+    root_entry.dev_requirement = false;
+    root_entry.installed = Some(InstalledPackage {
+        pretty_version: root_package.pretty_version.clone(),
+        version: root_package.version.clone(),
+        reference: root_package.reference.clone(),
+        package_type: root_package.package_type.clone(),
+        install_path: Some("../../".to_string()),
+        aliases: vec![],
+    });
+
+    let root_versions = vec![root_package.pretty_version.clone()];
+    add_installed_links(
+        &mut versions,
+        &root.replace.clone().unwrap_or_default(),
+        false,
+        &root_versions,
+        false,
+    );
+    add_installed_links(
+        &mut versions,
+        &root.provide.clone().unwrap_or_default(),
+        true,
+        &root_versions,
+        false,
+    );
+
+    for package in &packages {
+        let mut self_versions = vec![package.version.clone()];
+        self_versions.extend(package.aliases(&lock_aliases));
+        let is_dev = dev_package_names.contains(&package.name);
+
+        add_installed_links(
+            &mut versions,
+            &package.replace.clone().unwrap_or_default(),
+            false,
+            &self_versions,
+            is_dev,
+        );
+        add_installed_links(
+            &mut versions,
+            &package.provide.clone().unwrap_or_default(),
+            true,
+            &self_versions,
+            is_dev,
+        );
+    }
+
+    let template = InstalledTemplate {
+        root: root_package,
+        versions,
+        dev_mode,
+    };
+
+    let composer_directory = vendor_directory.join("composer");
+    let installed_file = composer_directory.join("installed.php");
+    tokio::fs::write(&installed_file, template.render().unwrap())
+        .await
+        .map_err(Error::io(&installed_file))?;
+
+    let installed_versions_file = composer_directory.join("InstalledVersions.php");
+    tokio::fs::write(
+        &installed_versions_file,
+        InstalledVersionsTemplate {}.render().unwrap(),
+    )
+    .await
+    .map_err(Error::io(&installed_versions_file))?;
 
     Ok(())
 }
 
+/// Replaced and provided packages count as installed, they are only dev requirements when all their providers are
+fn add_installed_links(
+    versions: &mut BTreeMap<String, InstalledVersion>,
+    links: &HashMap<String, String>,
+    provided: bool,
+    self_versions: &[String],
+    is_dev: bool,
+) {
+    for (name, constraint) in links {
+        if is_platform_package(name) {
+            continue;
+        }
+
+        let entry = versions
+            .entry(name.clone())
+            .or_insert_with(|| InstalledVersion {
+                dev_requirement: is_dev,
+                ..Default::default()
+            });
+        entry.dev_requirement &= is_dev;
+
+        let target = match provided {
+            true => &mut entry.provided,
+            false => &mut entry.replaced,
+        };
+
+        let constraints = match constraint.as_str() {
+            "self.version" => self_versions.to_vec(),
+            _ => vec![constraint.clone()],
+        };
+
+        for constraint in constraints {
+            if !target.contains(&constraint) {
+                target.push(constraint);
+            }
+        }
+    }
+}
+
+/// PHP, its extensions and system libraries or Composer's APIs, which are not installed packages
+fn is_platform_package(name: &str) -> bool {
+    name == "php"
+        || name == "composer"
+        || ["php-", "ext-", "lib-", "composer-"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
 mod filters {
     pub fn php_escape<T: std::fmt::Display>(s: T) -> ::askama::Result<String> {
-        let s = s.to_string();
-        Ok(s.replace("\\", "\\\\"))
+        Ok(super::php_escape(&s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_section(directory: &str) -> AutoloadSection {
+        AutoloadSection {
+            name: "acme/foo".to_string(),
+            autoload: serde_json::from_value(serde_json::json!({})).unwrap(),
+            directory: PathBuf::from(directory),
+            base: PathBase::Vendor,
+            prefix: "acme/foo".to_string(),
+            target_dir: None,
+        }
+    }
+
+    #[test]
+    fn classmap_builder_counts_ambiguous_classes() {
+        let mut builder = ClassmapBuilder::default();
+        builder.add(
+            &package_section("/vendor/acme/foo"),
+            [
+                ("Foo", "lib/Foo.php"),
+                ("Foo", "tests/Fixtures/Foo.php"),
+                ("Foo", "Examples/Foo.php"),
+                ("Bar", "a/Bar.php"),
+                ("Bar", "b/Bar.php"),
+                ("Bar", "c/Bar.php"),
+                ("Bar", "a/Bar.php"),
+            ]
+            .iter()
+            .map(|(class, file)| (class.to_string(), file.to_string()))
+            .collect(),
+        );
+
+        assert_eq!(
+            builder.ambiguous_classes,
+            HashSet::from(["Bar".to_string()])
+        );
+        assert_eq!(
+            builder.classmap["Foo"].variable_code(),
+            "$vendorDir . '/acme/foo/lib/Foo.php'"
+        );
+        assert_eq!(
+            builder.classmap["Bar"].variable_code(),
+            "$vendorDir . '/acme/foo/a/Bar.php'"
+        );
+    }
+
+    #[test]
+    fn autoload_sections_put_dependents_before_dependencies() {
+        let package = |name: &str, requires: &[&str]| {
+            serde_json::json!({
+                "name": name,
+                "version": "1.0.0",
+                "autoload": {"psr-4": {"Acme\\": "src/"}},
+                "require": requires.iter().map(|target| (*target, "*")).collect::<HashMap<_, _>>(),
+            })
+        };
+        let lock: lock::ComposerLock = serde_json::from_value(serde_json::json!({
+            "packages": [
+                package("acme/a", &[]),
+                package("acme/b", &["acme/c"]),
+                package("acme/c", &["acme/a"]),
+            ],
+            "content-hash": "abc123",
+        }))
+        .unwrap();
+        let root: composer_json::ComposerJson = serde_json::from_value(serde_json::json!({
+            "name": "acme/app",
+            "autoload": {"psr-4": {"App\\": "src/"}},
+        }))
+        .unwrap();
+
+        let sections = autoload_sections(&lock, &root, Path::new("/app/vendor"), false);
+
+        assert_eq!(
+            sections
+                .iter()
+                .map(|section| section.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["acme/app", "acme/b", "acme/c", "acme/a"]
+        );
+    }
+
+    #[test]
+    fn installed_links_skip_platform_packages() {
+        let links = HashMap::from([
+            ("php".to_string(), "*".to_string()),
+            ("php-64bit".to_string(), "*".to_string()),
+            ("ext-json".to_string(), "*".to_string()),
+            ("lib-icu".to_string(), "*".to_string()),
+            ("composer-plugin-api".to_string(), "*".to_string()),
+            ("psr/log-implementation".to_string(), "1.0".to_string()),
+        ]);
+
+        let mut versions = BTreeMap::new();
+        add_installed_links(&mut versions, &links, true, &[], false);
+
+        assert_eq!(
+            versions.keys().collect::<Vec<_>>(),
+            vec!["psr/log-implementation"]
+        );
+    }
+
+    #[test]
+    fn autoload_path_normalizes_segments() {
+        let path = AutoloadPath::new(PathBase::Vendor, "foo/bar/./src/../lib/");
+        assert_eq!(path.variable_code(), "$vendorDir . '/foo/bar/lib'");
+        assert_eq!(path.static_code(), "__DIR__ . '/..' . '/foo/bar/lib'");
+    }
+
+    #[test]
+    fn autoload_path_keeps_leading_parent_segments() {
+        let path = AutoloadPath::new(PathBase::Root, "../shared/src");
+        assert_eq!(path.variable_code(), "$baseDir . '/../shared/src'");
+        assert_eq!(path.static_code(), "__DIR__ . '/../..' . '/../shared/src'");
+
+        let path = AutoloadPath::new(PathBase::Root, "lib/../../../shared/./src");
+        assert_eq!(path.variable_code(), "$baseDir . '/../../shared/src'");
     }
 }
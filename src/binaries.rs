@@ -0,0 +1,318 @@
+use askama::Template;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Error, Result};
+use crate::lock;
+use crate::report::{Event, Reporter};
+
+#[derive(Template)]
+#[template(path = "bin_proxy_php.html", escape = "none")]
+struct PhpProxyTemplate {
+    bin_path: String,
+    bin_path_in_vendor: String,
+}
+
+#[derive(Template)]
+#[template(path = "bin_proxy_sh.html", escape = "none")]
+struct ShellProxyTemplate {
+    bin_directory: String,
+    bin_file: String,
+}
+
+pub fn bin_directory(vendor_directory: &Path) -> PathBuf {
+    vendor_directory.join("bin")
+}
+
+/// Creates vendor/bin entries for the `bin` of all packages according to `bin-compat`, like Composer's BinaryInstaller
+pub async fn install_binaries(
+    vendor_directory: &Path,
+    packages: &[lock::ComposerPackage],
+    bin_compat: &str,
+    reporter: &Reporter,
+) -> Result<()> {
+    let bin_directory = bin_directory(vendor_directory);
+    let mut linked = HashSet::new();
+
+    for package in packages {
+        for bin in package.bin.iter().flatten() {
+            let bin = bin.trim_start_matches("./");
+            let bin_path = vendor_directory.join(package.install_path()).join(bin);
+            let name = bin_name(package, bin)?;
+
+            if !bin_path.is_file() {
+                reporter.report(Event::BinarySkipped {
+                    package: package.name.clone(),
+                    bin: bin.to_string(),
+                    reason: "file not found in package".to_string(),
+                });
+                continue;
+            }
+
+            if !linked.insert(name.clone()) {
+                reporter.report(Event::BinarySkipped {
+                    package: package.name.clone(),
+                    bin: bin.to_string(),
+                    reason: "name conflicts with an existing file".to_string(),
+                });
+                continue;
+            }
+
+            tokio::fs::create_dir_all(&bin_directory)
+                .await
+                .map_err(Error::io(&bin_directory))?;
+
+            let link = bin_directory.join(&name);
+            let bin_path_in_vendor = format!("{}/{}", package.install_path(), bin);
+
+            remove_file_if_exists(&link).await?;
+            set_executable(&bin_path).await?;
+
+            if bin_compat == "symlink" && cfg!(unix) {
+                symlink_file(&Path::new("..").join(&bin_path_in_vendor), &link)
+                    .map_err(Error::io(&link))?;
+                continue;
+            }
+
+            let contents = tokio::fs::read(&bin_path)
+                .await
+                .map_err(Error::io(&bin_path))?;
+            let proxy = if is_php_file(&contents) {
+                PhpProxyTemplate {
+                    bin_path: format!("../{}", bin_path_in_vendor),
+                    bin_path_in_vendor: php_string(&bin_path_in_vendor),
+                }
+                .render()
+                .unwrap()
+            } else {
+                let (directory, file) = bin_path_in_vendor.rsplit_once('/').unwrap();
+                ShellProxyTemplate {
+                    bin_directory: format!("../{}", directory),
+                    bin_file: file.to_string(),
+                }
+                .render()
+                .unwrap()
+            };
+
+            tokio::fs::write(&link, proxy)
+                .await
+                .map_err(Error::io(&link))?;
+            set_executable(&link).await?;
+
+            if bin_compat == "full" || (bin_compat == "auto" && cfg!(windows)) {
+                let bat = bin_directory.join(format!("{}.bat", name));
+                tokio::fs::write(&bat, windows_proxy(&name, &contents))
+                    .await
+                    .map_err(Error::io(&bat))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the vendor/bin entries of a package which is uninstalled or replaced by another version
+pub async fn remove_binaries(
+    vendor_directory: &Path,
+    package: &lock::ComposerPackage,
+) -> Result<()> {
+    let bin_directory = bin_directory(vendor_directory);
+
+    for bin in package.bin.iter().flatten() {
+        let name = bin_name(package, bin)?;
+
+        remove_file_if_exists(&bin_directory.join(&name)).await?;
+        remove_file_if_exists(&bin_directory.join(format!("{}.bat", name))).await?;
+    }
+
+    if let Ok(mut entries) = tokio::fs::read_dir(&bin_directory).await {
+        let next_entry = entries
+            .next_entry()
+            .await
+            .map_err(Error::io(&bin_directory))?;
+        if next_entry.is_none() {
+            tokio::fs::remove_dir(&bin_directory)
+                .await
+                .map_err(Error::io(&bin_directory))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The vendor/bin entry of a bin is named after its file
+fn bin_name(package: &lock::ComposerPackage, bin: &str) -> Result<String> {
+    match Path::new(bin).file_name() {
+        Some(name) => Ok(name.to_string_lossy().to_string()),
+        None => Err(Error::package(
+            &package.name,
+            format!("bin {} does not name a file", bin),
+        )),
+    }
+}
+
+/// Escapes a value for a single-quoted PHP string the way `var_export` does
+fn php_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// Same detection as Composer, an optional shebang followed by an opening PHP tag
+fn is_php_file(contents: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&contents[..contents.len().min(500)]).to_string();
+    let without_shebang = match head.starts_with("#!") {
+        true => head.split_once('\n').map_or("", |(_, rest)| rest),
+        false => head.as_str(),
+    };
+
+    without_shebang.trim_start().starts_with("<?php")
+}
+
+fn windows_proxy(name: &str, contents: &[u8]) -> String {
+    let first_line = String::from_utf8_lossy(contents)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let caller = if name.ends_with(".bat") || name.ends_with(".exe") {
+        "call".to_string()
+    } else if let Some(interpreter) = first_line.strip_prefix("#!") {
+        let interpreter = interpreter.trim().trim_start_matches("/usr/bin/env ");
+        interpreter.rsplit('/').next().unwrap_or("php").to_string()
+    } else {
+        "php".to_string()
+    };
+
+    format!(
+        "@ECHO OFF\r\nsetlocal DISABLEDELAYEDEXPANSION\r\nSET BIN_TARGET=%~dp0/{}\r\nSET COMPOSER_RUNTIME_BIN_DIR=%~dp0\r\n{} \"%BIN_TARGET%\" %*\r\n",
+        name, caller
+    )
+}
+
+async fn remove_file_if_exists(path: &Path) -> Result<()> {
+    if tokio::fs::symlink_metadata(path).await.is_ok() {
+        tokio::fs::remove_file(path)
+            .await
+            .map_err(Error::io(path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = tokio::fs::metadata(path)
+        .await
+        .map_err(Error::io(path))?
+        .permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    tokio::fs::set_permissions(path, permissions)
+        .await
+        .map_err(Error::io(path))?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn set_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink_file(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(not(unix))]
+fn symlink_file(_original: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A vendor directory with acme/foo declaring the given bins, and its files
+    fn vendor_directory(
+        name: &str,
+        bins: &[&str],
+        files: &[(&str, &str)],
+    ) -> (PathBuf, Vec<lock::ComposerPackage>) {
+        let directory = std::env::temp_dir()
+            .join(format!("composer-rs-{}-{}", name, std::process::id()))
+            .join("vendor");
+        let _ = std::fs::remove_dir_all(&directory);
+
+        for (path, content) in files {
+            let file = directory.join("acme/foo").join(path);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, content).unwrap();
+        }
+
+        let package = serde_json::from_value(serde_json::json!({
+            "name": "acme/foo",
+            "version": "1.0.0",
+            "bin": bins,
+        }))
+        .unwrap();
+
+        (directory, vec![package])
+    }
+
+    #[tokio::test]
+    async fn install_binaries_creates_php_proxies() {
+        let (vendor, packages) = vendor_directory(
+            "bin-proxy",
+            &["bin/it's"],
+            &[("bin/it's", "#!/usr/bin/env php\n<?php echo 1;\n")],
+        );
+
+        install_binaries(&vendor, &packages, "proxy", &Reporter::default())
+            .await
+            .unwrap();
+
+        let proxy = std::fs::read_to_string(vendor.join("bin/it's")).unwrap();
+        assert!(proxy.starts_with("#!/usr/bin/env php\n"));
+        assert!(proxy.ends_with("return include __DIR__ . '/..'.'/acme/foo/bin/it\\'s';"));
+        assert!(!vendor.join("bin/it's.bat").exists());
+        std::fs::remove_dir_all(vendor.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn install_binaries_skips_missing_bins() {
+        let (vendor, packages) = vendor_directory("bin-missing", &["bin/missing"], &[]);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let reporter = {
+            let events = events.clone();
+            Reporter::new(move |event| events.lock().unwrap().push(event.to_string()))
+        };
+
+        install_binaries(&vendor, &packages, "auto", &reporter)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "Skipped installation of bin bin/missing for package acme/foo: file not found in package"
+            ]
+        );
+        assert!(!vendor.join("bin").exists());
+        let _ = std::fs::remove_dir_all(vendor.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn install_binaries_refuses_bins_without_a_file_name() {
+        let (vendor, packages) = vendor_directory("bin-invalid", &[".."], &[]);
+
+        let result = install_binaries(&vendor, &packages, "auto", &Reporter::default()).await;
+
+        assert!(matches!(result, Err(Error::Package { .. })));
+        let _ = std::fs::remove_dir_all(vendor.parent().unwrap());
+    }
+}
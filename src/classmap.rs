@@ -1,20 +1,110 @@
-use anyhow::Result;
 use async_walkdir::{Filtering, WalkDir};
 use futures_lite::stream::StreamExt;
-use php_parser_rs::parser::ast::{Statement, namespaces::NamespaceStatement};
-use std::{path::{Path, PathBuf}, collections::HashMap};
+use regex::Regex;
+use php_parser_rs::parser::ast::{
+    control_flow::IfStatementBody,
+    declares::DeclareBody,
+    loops::{ForStatementBody, ForeachStatementBody, WhileStatementBody},
+    namespaces::NamespaceStatement,
+    Statement,
+};
+use std::path::{Path, PathBuf};
 
+use crate::error::{Error, Result};
+use crate::php_scanner;
+use crate::report::{Event, Reporter};
+
+/// How PHP files are searched for classes
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ScanMode {
+    /// Strips strings and comments and matches declarations like Composer, fast and tolerant of any syntax
+    #[default]
+    Cleaner,
+    /// Builds the full syntax tree, files the parser does not support are skipped
+    Parser,
+}
+
+/// The `exclude-from-classmap` patterns of all packages, like Composer they apply to every
+/// classmap and are matched against real paths
+#[derive(Clone, Default)]
+pub struct ExcludePatterns {
+    regex: Option<Regex>,
+}
+
+impl ExcludePatterns {
+    /// Patterns with the directory of the package declaring them, `*` matches within a directory
+    /// and `**` across directories, a pattern excludes a file or a directory with all its contents
+    pub fn new(patterns: &[(PathBuf, String)]) -> Result<Self> {
+        let fragments = patterns
+            .iter()
+            .filter_map(|(package_directory, pattern)| exclude_fragment(package_directory, pattern))
+            .collect::<Vec<String>>();
+
+        if fragments.is_empty() {
+            return Ok(ExcludePatterns::default());
+        }
+
+        Ok(ExcludePatterns {
+            regex: Some(
+                Regex::new(&format!("({})", fragments.join("|")))
+                    .map_err(Error::ExcludeFromClassmap)?,
+            ),
+        })
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let Some(regex) = &self.regex else {
+            return false;
+        };
+
+        let real_path = path.canonicalize().unwrap_or(path.to_path_buf());
+        regex.is_match(&real_path.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+/// The regex of a pattern the way Composer's AutoloadGenerator builds it
+fn exclude_fragment(package_directory: &Path, pattern: &str) -> Option<String> {
+    let pattern = pattern.replace('\\', "/");
+    let mut pattern = regex::escape(pattern.trim_matches('/'));
+    while pattern.contains("//") {
+        pattern = pattern.replace("//", "/");
+    }
+    let pattern = pattern.replace("\\*\\*", ".+?").replace("\\*", "[^/]+?");
+
+    // Leading `../` and `./` segments are resolved against the package directory
+    let mut updir = String::new();
+    let mut pattern = pattern.as_str();
+    loop {
+        if let Some(rest) = pattern.strip_prefix("\\.\\./") {
+            updir.push_str("../");
+            pattern = rest;
+        } else if let Some(rest) = pattern.strip_prefix("\\./") {
+            updir.push_str("./");
+            pattern = rest;
+        } else {
+            break;
+        }
+    }
+
+    let resolved = package_directory.join(updir).canonicalize().ok()?;
+
+    Some(format!(
+        "{}/{}($|/)",
+        regex::escape(&resolved.to_string_lossy().replace('\\', "/")),
+        pattern
+    ))
+}
+
+/// Classes with the relative path of the file declaring them, files are scanned sorted by path so
+/// a class declared more than once is always listed in the same order
 pub async fn generate_classmap(
     package_directory: PathBuf,
     class_map_directory: String,
-    exclude_directories: Vec<String>,
-) -> Result<HashMap<String, String>> {
-    let mut class_to_files = HashMap::new();
-
-    let scoped_excludes = exclude_directories
-        .iter()
-        .map(|d| package_directory.join(d.as_str()))
-        .collect::<Vec<PathBuf>>();
+    excludes: &ExcludePatterns,
+    scan_mode: ScanMode,
+    reporter: &Reporter,
+) -> Result<Vec<(String, String)>> {
+    let mut class_to_files = vec![];
 
     let scan_directory = package_directory.join(Path::new(&class_map_directory));
 
@@ -23,57 +113,61 @@ pub async fn generate_classmap(
     }
 
     if scan_directory.is_file() {
+        if excludes.is_excluded(&scan_directory) {
+            return Ok(class_to_files);
+        }
+
         let read_file = scan_directory.clone();
         let relative_path = {
             let path = scan_directory.clone();
             path.strip_prefix(&package_directory).unwrap().to_str().unwrap().to_owned()
         };
-        let content = tokio::fs::read(read_file).await.expect("foo");
-
-        let parsed = php_parser_rs::parse(content.as_slice());
+        let content = tokio::fs::read(&read_file)
+            .await
+            .map_err(Error::io(&read_file))?;
 
-        match parsed {
-            Ok(parsed) => {
-                for name in get_classes_of_statements(parsed, "".to_string()) {
-                    class_to_files.insert(name, relative_path.clone());
-                }
-            }
-            Err(e) => {
-                println!("Error: {}", e);
-            }
+        for name in find_classes(&content, scan_mode, &relative_path, reporter) {
+            class_to_files.push((name, relative_path.clone()));
         }
 
         return Ok(class_to_files);
     }
 
-    let mut entries = WalkDir::new(scan_directory).filter(
-        |entry| async move {
-            let dir_path = entry.path();
-            let path = dir_path.to_str().unwrap();
-            if let Some(true) = dir_path
-                .file_name()
-                .map(|f| f.to_string_lossy().starts_with('.'))
-            {
-                return Filtering::IgnoreDir;
-            }
+    let walk_excludes = excludes.clone();
+    let mut entries = WalkDir::new(&scan_directory).filter(
+        move |entry| {
+            let excludes = walk_excludes.clone();
+            async move {
+                let dir_path = entry.path();
+                let path = dir_path.to_str().unwrap();
+                if let Some(true) = dir_path
+                    .file_name()
+                    .map(|f| f.to_string_lossy().starts_with('.'))
+                {
+                    return Filtering::IgnoreDir;
+                }
 
-            // if scoped_excludes.iter().any(|d| dir_path.starts_with(d)) {
-            //     return Filtering::IgnoreDir;
-            // }
+                // A directory matching a pattern has all its contents excluded as well
+                if excludes.is_excluded(&dir_path) {
+                    return Filtering::IgnoreDir;
+                }
 
-            // PHP files are not inside a node_modules
-            if entry.path().ends_with("node_modules") {
-                return Filtering::IgnoreDir;
-            }
+                // PHP files are not inside a node_modules
+                if entry.path().ends_with("node_modules") {
+                    return Filtering::IgnoreDir;
+                }
 
-            if !path.ends_with(".php") && !path.ends_with(".inc") {
-                return Filtering::Ignore;
-            }
+                if !path.ends_with(".php") && !path.ends_with(".inc") {
+                    return Filtering::Ignore;
+                }
 
-            Filtering::Continue
+                Filtering::Continue
+            }
         },
     );
 
+    let mut relative_paths = vec![];
+
     loop {
         match entries.next().await {
             Some(Ok(entry)) => {
@@ -81,31 +175,138 @@ pub async fn generate_classmap(
                     let path = entry.path();
                     path.strip_prefix(&package_directory).unwrap().to_str().unwrap().to_owned()
                 };
-                let content = tokio::fs::read(entry.path()).await.expect("foo");
-
-                let parsed = php_parser_rs::parse(content.as_slice());
-
-                match parsed {
-                    Ok(parsed) => {
-                        for name in get_classes_of_statements(parsed, "".to_string()) {
-                            class_to_files.insert(name, relative_path.clone());
-                        }
-                    }
-                    Err(e) => {
-                        println!("Error: {}", e);
-                    }
-                }
+                relative_paths.push(relative_path);
             }
             Some(Err(e)) => {
-                return Err(anyhow::anyhow!("Error: {}", e));
+                return Err(Error::Io {
+                    path: scan_directory,
+                    source: e,
+                });
             }
             None => break,
         }
     }
 
+    relative_paths.sort();
+
+    for relative_path in relative_paths {
+        let file = package_directory.join(&relative_path);
+        let content = tokio::fs::read(&file).await.map_err(Error::io(&file))?;
+
+        for name in find_classes(&content, scan_mode, &relative_path, reporter) {
+            class_to_files.push((name, relative_path.clone()));
+        }
+    }
+
     Ok(class_to_files)
 }
 
+/// Classes of a PSR-0 or PSR-4 directory which are located where the standard expects them, like
+/// Composer files declaring only misplaced classes are skipped and reported as violations
+pub async fn generate_psr_classmap(
+    package_directory: PathBuf,
+    directory: String,
+    namespace: &str,
+    standard: &str,
+    excludes: &ExcludePatterns,
+    scan_mode: ScanMode,
+    reporter: &Reporter,
+) -> Result<(Vec<(String, String)>, Vec<String>)> {
+    let base_directory = package_directory.join(&directory);
+    let classes = generate_classmap(
+        package_directory.clone(),
+        directory,
+        excludes,
+        scan_mode,
+        reporter,
+    )
+    .await?;
+
+    let mut valid_classes = vec![];
+    let mut violations = vec![];
+    let mut file_classes: Vec<(String, String)> = vec![];
+
+    for (index, (class, file)) in classes.iter().enumerate() {
+        file_classes.push((class.clone(), file.clone()));
+
+        // Classes are listed file by file, a file is complete when the next entry is another file
+        if classes.get(index + 1).is_some_and(|(_, next)| next == file) {
+            continue;
+        }
+
+        let file_path = package_directory.join(file);
+        let sub_path = file_path
+            .strip_prefix(&base_directory)
+            .unwrap_or(&file_path)
+            .with_extension("")
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let (valid, rejected): (Vec<_>, Vec<_>) = file_classes
+            .drain(..)
+            .filter(|(class, _)| class.starts_with(namespace))
+            .partition(|(class, _)| psr_path(class, namespace, standard) == sub_path);
+
+        if valid.is_empty() {
+            for (class, _) in rejected {
+                violations.push(format!(
+                    "Class {} located in {} does not comply with {} autoloading standard (rule: {} => {}). Skipping.",
+                    class,
+                    file_path.display(),
+                    standard,
+                    namespace,
+                    base_directory.display()
+                ));
+            }
+        }
+
+        valid_classes.extend(valid);
+    }
+
+    Ok((valid_classes, violations))
+}
+
+/// The path of a class relative to its PSR root, without the extension
+fn psr_path(class: &str, namespace: &str, standard: &str) -> String {
+    if standard == "psr-0" {
+        return match class.rsplit_once('\\') {
+            Some((class_namespace, class_name)) => {
+                format!(
+                    "{}/{}",
+                    class_namespace.replace('\\', "/"),
+                    class_name.replace('_', "/")
+                )
+            }
+            None => class.replace('_', "/"),
+        };
+    }
+
+    class[namespace.len()..].replace('\\', "/")
+}
+
+fn find_classes(
+    content: &[u8],
+    scan_mode: ScanMode,
+    path: &str,
+    reporter: &Reporter,
+) -> Vec<String> {
+    if scan_mode == ScanMode::Cleaner {
+        return php_scanner::find_classes(content);
+    }
+
+    match php_parser_rs::parse(content) {
+        Ok(parsed) => get_classes_of_statements(parsed, "".to_string()),
+        Err(e) => {
+            reporter.report(Event::ScanFailed {
+                path: path.to_string(),
+                message: e.to_string(),
+            });
+            vec![]
+        }
+    }
+}
+
+/// Collects classes, interfaces, traits and enums, also when declared conditionally like polyfills do
 fn get_classes_of_statements(statements: Vec<Statement>, prefix: String) -> Vec<String> {
     let mut classes = vec![];
     for stmt in statements {
@@ -113,26 +314,269 @@ fn get_classes_of_statements(statements: Vec<Statement>, prefix: String) -> Vec<
             Statement::Class(c) => {
                 classes.push(format!("{}{}", prefix, c.name));
             }
+            Statement::Interface(i) => {
+                classes.push(format!("{}{}", prefix, i.name));
+            }
+            Statement::Trait(t) => {
+                classes.push(format!("{}{}", prefix, t.name));
+            }
+            Statement::UnitEnum(e) => {
+                classes.push(format!("{}{}", prefix, e.name));
+            }
+            Statement::BackedEnum(e) => {
+                classes.push(format!("{}{}", prefix, e.name));
+            }
             Statement::Namespace(n) => {
                 match n {
                     NamespaceStatement::Braced(b) => {
                         let mut new_prefix = prefix.clone();
                         if let Some(name) = b.name {
                             new_prefix.push_str(std::str::from_utf8(&name.value.bytes).unwrap());
-                            new_prefix.push_str("\\");
+                            new_prefix.push('\\');
                         }
                         classes.append(&mut get_classes_of_statements(b.body.statements, new_prefix));
                     },
                     NamespaceStatement::Unbraced(u) => {
                         let mut new_prefix = prefix.clone();
                         new_prefix.push_str(std::str::from_utf8(&u.name.value.bytes).unwrap());
-                        new_prefix.push_str("\\");
+                        new_prefix.push('\\');
                         classes.append(&mut get_classes_of_statements(u.statements, new_prefix));
                     },
                 }
             }
-            _ => {}
+            stmt => {
+                classes.append(&mut get_classes_of_statements(nested_statements(stmt), prefix.clone()));
+            }
         }
     }
     classes
-}
\ No newline at end of file
+}
+
+/// Statements inside blocks, conditions, loops and function bodies, which can declare classes as well
+fn nested_statements(statement: Statement) -> Vec<Statement> {
+    match statement {
+        Statement::Block(b) => b.statements,
+        Statement::If(i) => match i.body {
+            IfStatementBody::Statement { statement, elseifs, r#else } => {
+                let mut statements = vec![*statement];
+                statements.extend(elseifs.into_iter().map(|e| *e.statement));
+                statements.extend(r#else.map(|e| *e.statement));
+                statements
+            }
+            IfStatementBody::Block { mut statements, elseifs, r#else, .. } => {
+                statements.extend(elseifs.into_iter().flat_map(|e| e.statements));
+                statements.extend(r#else.into_iter().flat_map(|e| e.statements));
+                statements
+            }
+        },
+        Statement::Try(t) => {
+            let mut statements = t.body;
+            statements.extend(t.catches.into_iter().flat_map(|c| c.body));
+            statements.extend(t.finally.into_iter().flat_map(|f| f.body));
+            statements
+        }
+        Statement::Switch(s) => s.cases.into_iter().flat_map(|c| c.body).collect(),
+        Statement::While(w) => match w.body {
+            WhileStatementBody::Statement { statement } => vec![*statement],
+            WhileStatementBody::Block { statements, .. } => statements,
+        },
+        Statement::DoWhile(d) => vec![*d.body],
+        Statement::For(f) => match f.body {
+            ForStatementBody::Statement { statement } => vec![*statement],
+            ForStatementBody::Block { statements, .. } => statements,
+        },
+        Statement::Foreach(f) => match f.body {
+            ForeachStatementBody::Statement { statement } => vec![*statement],
+            ForeachStatementBody::Block { statements, .. } => statements,
+        },
+        Statement::Declare(d) => match d.body {
+            DeclareBody::Braced { statements, .. } | DeclareBody::Block { statements, .. } => statements,
+            _ => vec![],
+        },
+        Statement::Function(f) => f.body.statements,
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("composer-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        for (path, content) in files {
+            let file = directory.join(path);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, content).unwrap();
+        }
+
+        directory
+    }
+
+    async fn psr_classmap(
+        directory: &Path,
+        namespace: &str,
+        standard: &str,
+    ) -> (Vec<String>, Vec<String>) {
+        let (classes, violations) = generate_psr_classmap(
+            directory.to_path_buf(),
+            "src".to_string(),
+            namespace,
+            standard,
+            &ExcludePatterns::default(),
+            ScanMode::Cleaner,
+            &Reporter::default(),
+        )
+        .await
+        .unwrap();
+
+        (
+            classes.into_iter().map(|(class, _)| class).collect(),
+            violations,
+        )
+    }
+
+    fn excluded(pattern: &str, files: &[&str]) -> Vec<String> {
+        let directory = package_directory(
+            &format!("exclude-{}", pattern.replace(['/', '*'], "_")),
+            &files
+                .iter()
+                .map(|file| (*file, "<?php"))
+                .collect::<Vec<_>>(),
+        );
+        let excludes = ExcludePatterns::new(&[(directory.clone(), pattern.to_string())]).unwrap();
+
+        let excluded = files
+            .iter()
+            .filter(|file| excludes.is_excluded(&directory.join(file)))
+            .map(|file| file.to_string())
+            .collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+        excluded
+    }
+
+    #[test]
+    fn exclude_patterns_match_within_a_directory_with_a_single_star() {
+        assert_eq!(
+            excluded(
+                "src/*/Tests",
+                &[
+                    "src/Foo/Tests/A.php",
+                    "src/Foo/Bar/Tests/A.php",
+                    "src/Tests/A.php"
+                ]
+            ),
+            vec!["src/Foo/Tests/A.php"]
+        );
+    }
+
+    #[test]
+    fn exclude_patterns_match_across_directories_with_a_double_star() {
+        assert_eq!(
+            excluded(
+                "src/**/Tests",
+                &[
+                    "src/Foo/Tests/A.php",
+                    "src/Foo/Bar/Tests/A.php",
+                    "src/Tests/A.php"
+                ]
+            ),
+            vec!["src/Foo/Tests/A.php", "src/Foo/Bar/Tests/A.php"]
+        );
+    }
+
+    #[test]
+    fn exclude_patterns_with_a_trailing_slash_match_the_directory() {
+        assert_eq!(
+            excluded(
+                "src/Legacy/",
+                &[
+                    "src/Legacy/A.php",
+                    "src/Legacy/Old/B.php",
+                    "src/LegacyC.php"
+                ]
+            ),
+            vec!["src/Legacy/A.php", "src/Legacy/Old/B.php"]
+        );
+    }
+
+    #[test]
+    fn psr_paths() {
+        assert_eq!(psr_path("Foo\\Bar\\Baz", "Foo\\", "psr-4"), "Bar/Baz");
+        assert_eq!(psr_path("Foo\\Bar_Baz", "Foo\\", "psr-4"), "Bar_Baz");
+        assert_eq!(
+            psr_path("Foo\\Bar\\Baz_Qux", "Foo\\", "psr-0"),
+            "Foo/Bar/Baz/Qux"
+        );
+        assert_eq!(
+            psr_path("Twig_Node_Expression", "Twig_", "psr-0"),
+            "Twig/Node/Expression"
+        );
+    }
+
+    #[tokio::test]
+    async fn psr4_classmap_reports_misplaced_classes() {
+        let directory = package_directory(
+            "psr4-classmap",
+            &[
+                ("src/Bar.php", "<?php namespace Foo; class Bar {}"),
+                ("src/Sub/Baz.php", "<?php namespace Foo\\Sub; class Baz {}"),
+                ("src/WrongName.php", "<?php namespace Foo; class Other {}"),
+                (
+                    "src/Sub/WrongNamespace.php",
+                    "<?php namespace Foo\\Other; class WrongNamespace {}",
+                ),
+                (
+                    "src/Unrelated.php",
+                    "<?php namespace Unrelated; class Unrelated {}",
+                ),
+            ],
+        );
+
+        let (classes, violations) = psr_classmap(&directory, "Foo\\", "psr-4").await;
+
+        assert_eq!(classes, vec!["Foo\\Bar", "Foo\\Sub\\Baz"]);
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].starts_with("Class Foo\\Other\\WrongNamespace located in "));
+        let rule = format!(
+            "does not comply with psr-4 autoloading standard (rule: Foo\\ => {}). Skipping.",
+            directory.join("src").display()
+        );
+        assert!(violations[0].ends_with(&rule));
+        assert!(violations[1].starts_with("Class Foo\\Other located in "));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn psr0_classmap_maps_underscores_to_directories() {
+        let directory = package_directory(
+            "psr0-classmap",
+            &[
+                (
+                    "src/Twig/Node/Expression.php",
+                    "<?php class Twig_Node_Expression {}",
+                ),
+                ("src/Twig/Node.php", "<?php class Twig_Node_Expression {}"),
+                (
+                    "src/Twig/Ns/Foo_Bar.php",
+                    "<?php namespace Twig\\Ns; class Foo_Bar {}",
+                ),
+                (
+                    "src/Twig/Ns/Foo/Baz.php",
+                    "<?php namespace Twig\\Ns; class Foo_Baz {}",
+                ),
+            ],
+        );
+
+        let (classes, violations) = psr_classmap(&directory, "Twig", "psr-0").await;
+
+        assert_eq!(classes, vec!["Twig_Node_Expression", "Twig\\Ns\\Foo_Baz"]);
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].starts_with("Class Twig_Node_Expression located in "));
+        assert!(violations[1].starts_with("Class Twig\\Ns\\Foo_Bar located in "));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
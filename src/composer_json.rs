@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::lock;
+
+/// `platform-check` is a boolean or "php-only" to skip the checks of extensions
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum PlatformCheck {
+    Enabled(bool),
+    Mode(String),
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct ComposerConfig {
+    #[serde(alias = "bin-compat")]
+    pub bin_compat: Option<String>,
+    #[serde(alias = "autoloader-suffix")]
+    pub autoloader_suffix: Option<String>,
+    #[serde(alias = "prepend-autoloader")]
+    pub prepend_autoloader: Option<bool>,
+    #[serde(alias = "platform-check")]
+    pub platform_check: Option<PlatformCheck>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct ComposerJson {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    #[serde(alias = "type")]
+    pub package_type: Option<String>,
+    pub require: Option<HashMap<String, String>>,
+    pub replace: Option<HashMap<String, String>>,
+    pub provide: Option<HashMap<String, String>>,
+    pub autoload: Option<lock::ComposerAutoload>,
+    #[serde(alias = "autoload-dev")]
+    pub autoload_dev: Option<lock::ComposerAutoload>,
+    #[serde(alias = "include-path")]
+    pub include_path: Option<Vec<String>>,
+    pub config: Option<ComposerConfig>,
+}
+
+impl ComposerJson {
+    pub fn root_name(&self) -> String {
+        self.name.clone().unwrap_or("__root__".to_string())
+    }
+
+    pub fn config(&self) -> ComposerConfig {
+        self.config.clone().unwrap_or_default()
+    }
+}
+
+/// Loads the root composer.json, projects without one are treated like an empty root package
+pub async fn load_composer_json(file_path: PathBuf) -> Result<ComposerJson> {
+    if !file_path.exists() {
+        return Ok(ComposerJson::default());
+    }
+
+    let content = tokio::fs::read(&file_path)
+        .await
+        .map_err(Error::io(&file_path))?;
+
+    let parsed = serde_json::from_slice(&content).map_err(Error::json(&file_path))?;
+
+    Ok(parsed)
+}
+
+/// Guesses the version and commit of the root package like Composer, from COMPOSER_ROOT_VERSION, composer.json or git
+pub fn guess_root_version(
+    composer_json: &ComposerJson,
+    working_directory: &Path,
+) -> (String, Option<String>) {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .current_dir(working_directory)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|output| !output.is_empty())
+    };
+
+    let reference = git(&["rev-parse", "HEAD"]);
+
+    if let Ok(version) = std::env::var("COMPOSER_ROOT_VERSION") {
+        if !version.is_empty() {
+            return (version, reference);
+        }
+    }
+
+    if let Some(version) = &composer_json.version {
+        return (version.clone(), reference);
+    }
+
+    let version = match git(&["rev-parse", "--abbrev-ref", "HEAD"]) {
+        Some(branch) if branch != "HEAD" => format!("dev-{}", branch),
+        _ => match &reference {
+            Some(reference) => format!("dev-{}", reference),
+            None => "1.0.0+no-version-set".to_string(),
+        },
+    };
+
+    (version, reference)
+}
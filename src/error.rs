@@ -0,0 +1,110 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while installing packages or generating the autoloader
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A file or directory could not be read or written
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A JSON file like composer.lock or installed.json is not valid
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// The HTTP client for downloads could not be set up
+    HttpClient(reqwest::Error),
+    /// A package archive could not be downloaded
+    Download { url: String, source: reqwest::Error },
+    /// A downloaded archive does not match the `shasum` of the lock
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    /// A package archive is corrupt or contains paths outside of the package
+    Extract { path: PathBuf, message: String },
+    /// A git command for a `source` installation failed
+    Git { command: String, message: String },
+    /// A package cannot be installed from what the lock says about it
+    Package { name: String, message: String },
+    /// The `exclude-from-classmap` patterns do not make a valid regex, e.g. because it is too big
+    ExcludeFromClassmap(regex::Error),
+    /// PSR-0 or PSR-4 directories contain classes where the standard does not expect them, only with `strict_psr`
+    PsrViolations(usize),
+    /// Classes are declared in more than one file, only with `strict_ambiguous`
+    AmbiguousClasses(usize),
+}
+
+impl Error {
+    /// Maps an I/O error to the path it happened on, e.g. `.map_err(Error::io(&path))`
+    pub(crate) fn io(path: impl AsRef<Path>) -> impl FnOnce(std::io::Error) -> Error {
+        let path = path.as_ref().to_path_buf();
+        move |source| Error::Io { path, source }
+    }
+
+    pub(crate) fn json(path: impl AsRef<Path>) -> impl FnOnce(serde_json::Error) -> Error {
+        let path = path.as_ref().to_path_buf();
+        move |source| Error::Json { path, source }
+    }
+
+    pub(crate) fn package(name: &str, message: impl fmt::Display) -> Error {
+        Error::Package {
+            name: name.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// The underlying I/O, JSON, HTTP or regex error is not repeated here, it is the `source` of the error
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, .. } => write!(f, "Failed to access {}", path.display()),
+            Error::Json { path, .. } => write!(f, "Failed to parse {}", path.display()),
+            Error::HttpClient(_) => write!(f, "Failed to create the HTTP client"),
+            Error::Download { url, .. } => write!(f, "Failed to download {}", url),
+            Error::ChecksumMismatch {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {}: expected {}, got {}",
+                url, expected, actual
+            ),
+            Error::Extract { path, message } => {
+                write!(f, "Failed to extract {}: {}", path.display(), message)
+            }
+            Error::Git { command, message } => write!(f, "git {} failed: {}", command, message),
+            Error::Package { name, message } => write!(f, "{}: {}", name, message),
+            Error::ExcludeFromClassmap(_) => write!(f, "Invalid exclude-from-classmap patterns"),
+            Error::PsrViolations(count) => write!(
+                f,
+                "PSR-0/PSR-4 autoloading standard violations found: {}",
+                count
+            ),
+            Error::AmbiguousClasses(count) => {
+                write!(f, "Ambiguous class resolution found for {} classes", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::HttpClient(source) => Some(source),
+            Error::Download { source, .. } => Some(source),
+            Error::ExcludeFromClassmap(source) => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::lock;
+use crate::version;
+
+/// vendor/composer/installed.json, Composer 1 wrote a plain list of packages
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InstalledJson {
+    Composer2 {
+        packages: Vec<lock::ComposerPackage>,
+    },
+    Composer1(Vec<lock::ComposerPackage>),
+}
+
+pub enum Operation {
+    Install(lock::ComposerPackage),
+    Update(Box<lock::ComposerPackage>, lock::ComposerPackage),
+    Uninstall(lock::ComposerPackage),
+}
+
+pub fn installed_json_path(vendor_directory: &Path) -> PathBuf {
+    vendor_directory.join("composer").join("installed.json")
+}
+
+/// Packages installed by a previous run, an empty list when nothing was installed yet
+pub async fn load_installed_packages(
+    vendor_directory: &Path,
+) -> Result<Vec<lock::ComposerPackage>> {
+    let installed_file = installed_json_path(vendor_directory);
+
+    if !installed_file.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = tokio::fs::read(&installed_file)
+        .await
+        .map_err(Error::io(&installed_file))?;
+
+    let installed = match serde_json::from_slice(&content).map_err(Error::json(&installed_file))? {
+        InstalledJson::Composer2 { packages } => packages,
+        InstalledJson::Composer1(packages) => packages,
+    };
+
+    Ok(installed)
+}
+
+/// Whether the last install included dev packages, none when it is not recorded
+pub async fn load_installed_dev_mode(vendor_directory: &Path) -> Option<bool> {
+    let content = tokio::fs::read(installed_json_path(vendor_directory))
+        .await
+        .ok()?;
+    let installed: serde_json::Value = serde_json::from_slice(&content).ok()?;
+
+    installed.get("dev")?.as_bool()
+}
+
+/// Compares the installed packages with the lock, unchanged packages produce no operation
+pub fn compute_operations(
+    installed: &[lock::ComposerPackage],
+    packages: &[lock::ComposerPackage],
+    vendor_directory: &Path,
+) -> Vec<Operation> {
+    let mut operations = vec![];
+
+    // Like Composer packages which are no longer locked are uninstalled first
+    for previous in installed {
+        if !packages.iter().any(|p| p.name == previous.name) {
+            operations.push(Operation::Uninstall(previous.clone()));
+        }
+    }
+
+    for package in packages {
+        let previous = installed.iter().find(|p| p.name == package.name);
+
+        match previous {
+            // A package removed by hand from vendor is installed again
+            Some(_)
+                if !is_metapackage(package) && !vendor_directory.join(&package.name).exists() =>
+            {
+                operations.push(Operation::Install(package.clone()));
+            }
+            Some(previous) if is_same_package(previous, package) => continue,
+            Some(previous) => operations.push(Operation::Update(
+                Box::new(previous.clone()),
+                package.clone(),
+            )),
+            None => operations.push(Operation::Install(package.clone())),
+        }
+    }
+
+    operations
+}
+
+fn is_same_package(a: &lock::ComposerPackage, b: &lock::ComposerPackage) -> bool {
+    let reference =
+        |source: &Option<lock::ComposerPackageSource>| source.as_ref().map(|s| s.reference.clone());
+
+    a.version == b.version
+        && reference(&a.source) == reference(&b.source)
+        && reference(&a.dist) == reference(&b.dist)
+}
+
+pub fn is_metapackage(package: &lock::ComposerPackage) -> bool {
+    package
+        .package_type
+        .as_ref()
+        .is_some_and(|package_type| package_type == "metapackage")
+}
+
+/// Keys which Composer dumps before `installation-source`, it is inserted after the last of them
+const KEYS_BEFORE_INSTALLATION_SOURCE: [&str; 16] = [
+    "name",
+    "version",
+    "version_normalized",
+    "target-dir",
+    "source",
+    "dist",
+    "require",
+    "conflict",
+    "provide",
+    "replace",
+    "require-dev",
+    "suggest",
+    "time",
+    "bin",
+    "type",
+    "extra",
+];
+
+/// Writes installed.json in the format of Composer 2, which other tools read to discover packages
+pub async fn write_installed_packages(
+    vendor_directory: &Path,
+    packages: &[lock::ComposerPackage],
+    dev_package_names: &[String],
+    dev_mode: bool,
+    prefer_source: bool,
+) -> Result<()> {
+    // Like Composer packages are sorted by name, independently of the install order
+    let mut sorted_packages = packages.iter().collect::<Vec<&lock::ComposerPackage>>();
+    sorted_packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let installed = sorted_packages
+        .into_iter()
+        .map(|package| {
+            let mut entries = installed_package_json(package)
+                .into_iter()
+                .collect::<Vec<(String, serde_json::Value)>>();
+
+            // Composer 2 always writes the normalized version right after the version
+            if !entries.iter().any(|(key, _)| key == "version_normalized") {
+                let position = entries
+                    .iter()
+                    .position(|(key, _)| key == "version")
+                    .map_or(entries.len(), |position| position + 1);
+
+                entries.insert(
+                    position,
+                    (
+                        "version_normalized".to_string(),
+                        json!(version::normalize_version(&package.version)),
+                    ),
+                );
+            }
+
+            if let Some(installation_source) = package.installation_source(prefer_source) {
+                let position = entries
+                    .iter()
+                    .rposition(|(key, _)| KEYS_BEFORE_INSTALLATION_SOURCE.contains(&key.as_str()))
+                    .map_or(0, |position| position + 1);
+
+                entries.insert(
+                    position,
+                    (
+                        "installation-source".to_string(),
+                        json!(installation_source),
+                    ),
+                );
+            }
+
+            let mut json = entries
+                .into_iter()
+                .collect::<serde_json::Map<String, serde_json::Value>>();
+            // Metapackages are never written to disk and have no install path
+            let install_path = match is_metapackage(package) {
+                true => serde_json::Value::Null,
+                false => json!(format!("../{}", package.install_path())),
+            };
+            json.insert("install-path".to_string(), install_path);
+
+            serde_json::Value::Object(json)
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    let mut installed_dev_package_names = dev_package_names
+        .iter()
+        .filter(|name| packages.iter().any(|p| &&p.name == name))
+        .collect::<Vec<&String>>();
+    installed_dev_package_names.sort();
+
+    let content = json!({
+        "packages": installed,
+        "dev": dev_mode,
+        "dev-package-names": installed_dev_package_names,
+    });
+
+    let installed_file = installed_json_path(vendor_directory);
+    let composer_directory = installed_file.parent().unwrap();
+    tokio::fs::create_dir_all(composer_directory)
+        .await
+        .map_err(Error::io(composer_directory))?;
+
+    tokio::fs::write(&installed_file, encode_json(&content))
+        .await
+        .map_err(Error::io(&installed_file))?;
+
+    Ok(())
+}
+
+/// The lock entry of a package, packages only known by their modeled fields are reconstructed from them
+fn installed_package_json(
+    package: &lock::ComposerPackage,
+) -> serde_json::Map<String, serde_json::Value> {
+    if !package.json.is_empty() {
+        return package.json.clone();
+    }
+
+    let source_json = |source: &lock::ComposerPackageSource| {
+        json!({
+            "type": source.source_type,
+            "url": source.url,
+            "reference": source.reference,
+            "shasum": source.shasum,
+        })
+    };
+
+    let mut json = serde_json::Map::new();
+    json.insert("name".to_string(), json!(package.name));
+    json.insert("version".to_string(), json!(package.version));
+    if let Some(source) = &package.source {
+        json.insert("source".to_string(), source_json(source));
+    }
+    if let Some(dist) = &package.dist {
+        json.insert("dist".to_string(), source_json(dist));
+    }
+    if let Some(package_type) = &package.package_type {
+        json.insert("type".to_string(), json!(package_type));
+    }
+
+    json
+}
+
+/// Same output as Composer's JsonFile: four spaces of indentation and a trailing newline
+pub fn encode_json(value: &serde_json::Value) -> String {
+    let mut buffer = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+    // A JSON value always serializes to valid UTF-8
+    value.serialize(&mut serializer).unwrap();
+
+    let mut encoded = String::from_utf8(buffer).unwrap();
+    encoded.push('\n');
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(value: serde_json::Value) -> lock::ComposerPackage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn locked(name: &str, version: &str, reference: &str) -> lock::ComposerPackage {
+        package(json!({
+            "name": name,
+            "version": version,
+            "dist": {"type": "zip", "url": "https://example.com/package.zip", "reference": reference},
+        }))
+    }
+
+    fn operations(
+        installed: &[lock::ComposerPackage],
+        packages: &[lock::ComposerPackage],
+        installed_directories: &[&str],
+    ) -> Vec<String> {
+        let vendor_directory = std::env::temp_dir().join(format!(
+            "composer-rs-operations-{}-{}",
+            std::process::id(),
+            installed_directories.join("-").replace('/', "_")
+        ));
+        let _ = std::fs::remove_dir_all(&vendor_directory);
+        for name in installed_directories {
+            std::fs::create_dir_all(vendor_directory.join(name)).unwrap();
+        }
+
+        let operations = compute_operations(installed, packages, &vendor_directory)
+            .into_iter()
+            .map(|operation| match operation {
+                Operation::Install(package) => format!("install {}", package.name),
+                Operation::Update(from, to) => {
+                    format!("update {} {} => {}", to.name, from.version, to.version)
+                }
+                Operation::Uninstall(package) => format!("uninstall {}", package.name),
+            })
+            .collect();
+
+        let _ = std::fs::remove_dir_all(&vendor_directory);
+        operations
+    }
+
+    #[test]
+    fn compute_operations_uninstalls_unlocked_packages_first() {
+        let installed = [
+            locked("acme/a", "1.0.0", "a1"),
+            locked("acme/b", "1.0.0", "b1"),
+        ];
+        let packages = [
+            locked("acme/a", "1.0.0", "a1"),
+            locked("acme/c", "1.0.0", "c1"),
+        ];
+
+        assert_eq!(
+            operations(&installed, &packages, &["acme/a", "acme/b"]),
+            vec!["uninstall acme/b", "install acme/c"]
+        );
+    }
+
+    #[test]
+    fn compute_operations_updates_changed_packages() {
+        let installed = [
+            locked("acme/a", "1.0.0", "a1"),
+            locked("acme/b", "dev-main", "b1"),
+            locked("acme/c", "1.0.0", "c1"),
+        ];
+        let packages = [
+            locked("acme/a", "1.1.0", "a2"),
+            locked("acme/b", "dev-main", "b2"),
+            locked("acme/c", "1.0.0", "c1"),
+        ];
+
+        assert_eq!(
+            operations(&installed, &packages, &["acme/a", "acme/b", "acme/c"]),
+            vec![
+                "update acme/a 1.0.0 => 1.1.0",
+                "update acme/b dev-main => dev-main"
+            ]
+        );
+    }
+
+    #[test]
+    fn compute_operations_reinstalls_deleted_packages() {
+        let installed = [
+            locked("acme/a", "1.0.0", "a1"),
+            package(json!({"name": "acme/meta", "version": "1.0.0", "type": "metapackage"})),
+        ];
+        let packages = installed.clone();
+
+        assert_eq!(
+            operations(&installed, &packages, &[]),
+            vec!["install acme/a"]
+        );
+        assert!(operations(&installed, &packages, &["acme/a"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn write_installed_packages_in_the_composer_2_format() {
+        let vendor_directory =
+            std::env::temp_dir().join(format!("composer-rs-installed-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&vendor_directory);
+
+        let packages = vec![
+            package(json!({
+                "name": "acme/zz",
+                "version": "v1.2",
+                "dist": {"type": "zip", "url": "https://example.com/zz.zip", "reference": "abc"},
+                "type": "library",
+            })),
+            package(json!({
+                "name": "acme/dev-tool",
+                "version": "2.x-dev",
+                "source": {"type": "git", "url": "https://example.com/dev-tool.git", "reference": "def"},
+                "dist": {"type": "zip", "url": "https://example.com/dev-tool.zip", "reference": "def"},
+            })),
+            package(json!({"name": "acme/meta", "version": "1.0.0", "type": "metapackage"})),
+        ];
+        let dev_package_names = vec![
+            "acme/meta".to_string(),
+            "acme/dev-tool".to_string(),
+            "acme/uninstalled".to_string(),
+        ];
+
+        write_installed_packages(
+            &vendor_directory,
+            &packages,
+            &dev_package_names,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let content = std::fs::read_to_string(installed_json_path(&vendor_directory)).unwrap();
+        let installed: serde_json::Value = serde_json::from_str(&content).unwrap();
+        let installed_packages = installed["packages"].as_array().unwrap();
+
+        let keys = |index: usize| {
+            installed_packages[index]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            keys(0),
+            vec![
+                "name",
+                "version",
+                "version_normalized",
+                "source",
+                "dist",
+                "installation-source",
+                "install-path"
+            ]
+        );
+        assert_eq!(
+            keys(2),
+            vec![
+                "name",
+                "version",
+                "version_normalized",
+                "dist",
+                "type",
+                "installation-source",
+                "install-path"
+            ]
+        );
+
+        assert_eq!(
+            installed_packages
+                .iter()
+                .map(|package| package["name"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["acme/dev-tool", "acme/meta", "acme/zz"]
+        );
+        assert_eq!(
+            installed_packages[0]["version_normalized"],
+            "2.9999999.9999999.9999999-dev"
+        );
+        assert_eq!(installed_packages[2]["version_normalized"], "1.2.0.0");
+        assert_eq!(installed_packages[0]["installation-source"], "dist");
+        assert_eq!(installed_packages[0]["install-path"], "../acme/dev-tool");
+        assert_eq!(
+            installed_packages[1]["install-path"],
+            serde_json::Value::Null
+        );
+        assert_eq!(installed["dev"], true);
+        assert_eq!(
+            installed["dev-package-names"],
+            json!(["acme/dev-tool", "acme/meta"])
+        );
+
+        std::fs::remove_dir_all(&vendor_directory).unwrap();
+    }
+}
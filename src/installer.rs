@@ -0,0 +1,895 @@
+use sha1::{Digest, Sha1};
+use std::{
+    ffi::OsStr,
+    io::{Cursor, Read},
+    path::{Component, Path, PathBuf},
+};
+use tokio::{fs::File, io::AsyncReadExt, process::Command};
+
+use crate::error::{Error, Result};
+use crate::lock;
+use crate::report::{Event, Reporter};
+use crate::InstallOptions;
+
+pub async fn install_package(
+    name: String,
+    client: reqwest::Client,
+    source: lock::ComposerPackageSource,
+    transport_options: Option<lock::ComposerTransportOptions>,
+    options: InstallOptions,
+    extract: PathBuf,
+) -> Result<()> {
+    let cache_archive_directory = options.cache_directory.join("archives");
+    let cache_vcs_directory = options.cache_directory.join("vcs");
+    let reporter = &options.reporter;
+
+    match source.source_type.as_str() {
+        "zip" => {
+            install_package_from_zip(client, source, cache_archive_directory, extract, reporter)
+                .await
+        }
+        "tar" | "tgz" | "tar.gz" | "tar.bz2" | "tar.xz" => {
+            install_package_from_tar(client, source, cache_archive_directory, extract, reporter)
+                .await
+        }
+        "git" => install_package_from_git(source, cache_vcs_directory, extract).await,
+        "path" => {
+            install_package_from_path(
+                source,
+                transport_options,
+                options.working_directory,
+                extract,
+            )
+            .await
+        }
+        source_type => Err(Error::package(
+            &name,
+            format!("Unsupported source type: {}", source_type),
+        )),
+    }
+}
+
+/// Removes an installed package, symlinked path packages only lose their link
+pub async fn remove_package(package_directory: &Path) -> Result<()> {
+    remove_package_directory(package_directory).await?;
+
+    // The vendor namespace directory is dropped together with its last package
+    if let Some(parent) = package_directory.parent() {
+        if let Ok(mut entries) = tokio::fs::read_dir(parent).await {
+            let next_entry = entries.next_entry().await.map_err(Error::io(parent))?;
+            if next_entry.is_none() {
+                tokio::fs::remove_dir(parent)
+                    .await
+                    .map_err(Error::io(parent))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the directory or link of a package, but never its vendor namespace directory which
+/// other packages may be installed into at the same time
+async fn remove_package_directory(package_directory: &Path) -> Result<()> {
+    if let Ok(metadata) = tokio::fs::symlink_metadata(package_directory).await {
+        if metadata.is_dir() {
+            tokio::fs::remove_dir_all(package_directory)
+                .await
+                .map_err(Error::io(package_directory))?;
+        } else {
+            tokio::fs::remove_file(package_directory)
+                .await
+                .map_err(Error::io(package_directory))?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn install_package_from_zip(
+    client: reqwest::Client,
+    source: lock::ComposerPackageSource,
+    cache_directory: PathBuf,
+    extract: PathBuf,
+    reporter: &Reporter,
+) -> Result<()> {
+    let cache_file = cache_directory.join(Path::new(format!("{}.zip", source.reference).as_str()));
+    let archive = fetch_archive(client, &source, cache_file, reporter).await?;
+
+    tokio::fs::create_dir_all(&extract)
+        .await
+        .map_err(Error::io(&extract))?;
+
+    zip_extract::extract(Cursor::new(archive), &extract, true).map_err(|e| Error::Extract {
+        path: extract.clone(),
+        message: e.to_string(),
+    })?;
+
+    Ok(())
+}
+
+async fn install_package_from_tar(
+    client: reqwest::Client,
+    source: lock::ComposerPackageSource,
+    cache_directory: PathBuf,
+    extract: PathBuf,
+    reporter: &Reporter,
+) -> Result<()> {
+    let cache_file = cache_directory.join(Path::new(format!("{}.tar", source.reference).as_str()));
+    let archive = fetch_archive(client, &source, cache_file, reporter).await?;
+
+    tokio::fs::create_dir_all(&extract)
+        .await
+        .map_err(Error::io(&extract))?;
+
+    decompress_tar(archive)
+        .and_then(|archive| extract_tar(&archive, &extract))
+        .map_err(|e| Error::Extract {
+            path: extract.clone(),
+            message: e.to_string(),
+        })
+}
+
+async fn install_package_from_git(
+    source: lock::ComposerPackageSource,
+    cache_directory: PathBuf,
+    extract: PathBuf,
+) -> Result<()> {
+    let mirror = cache_directory.join(git_cache_key(&source.url));
+
+    tokio::fs::create_dir_all(&cache_directory)
+        .await
+        .map_err(Error::io(&cache_directory))?;
+
+    // The bare mirror is only updated when it does not know the locked commit yet
+    if !mirror.exists() {
+        run_git(
+            &[
+                OsStr::new("clone"),
+                OsStr::new("--mirror"),
+                OsStr::new("--quiet"),
+                OsStr::new("--"),
+                OsStr::new(&source.url),
+                mirror.as_os_str(),
+            ],
+            &cache_directory,
+        )
+        .await?;
+    } else if !has_git_commit(&mirror, &source.reference).await {
+        run_git(
+            &["remote", "set-url", "origin", "--", source.url.as_str()],
+            &mirror,
+        )
+        .await?;
+        run_git(&["remote", "update", "--prune", "origin"], &mirror).await?;
+    }
+
+    if !has_git_commit(&mirror, &source.reference).await {
+        return Err(Error::Git {
+            command: format!("checkout {}", source.reference),
+            message: format!(
+                "Reference {} does not exist in {}",
+                source.reference, source.url
+            ),
+        });
+    }
+
+    if extract.exists() {
+        tokio::fs::remove_dir_all(&extract)
+            .await
+            .map_err(Error::io(&extract))?;
+    }
+
+    tokio::fs::create_dir_all(&extract)
+        .await
+        .map_err(Error::io(&extract))?;
+
+    run_git(
+        &[
+            OsStr::new("clone"),
+            OsStr::new("--no-checkout"),
+            OsStr::new("--quiet"),
+            OsStr::new("--"),
+            mirror.as_os_str(),
+            extract.as_os_str(),
+        ],
+        &cache_directory,
+    )
+    .await?;
+    run_git(
+        &["remote", "set-url", "origin", "--", source.url.as_str()],
+        &extract,
+    )
+    .await?;
+    run_git(
+        &["checkout", "--quiet", "--detach", source.reference.as_str()],
+        &extract,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn install_package_from_path(
+    source: lock::ComposerPackageSource,
+    transport_options: Option<lock::ComposerTransportOptions>,
+    working_directory: PathBuf,
+    extract: PathBuf,
+) -> Result<()> {
+    let package_directory = working_directory.join(&source.url);
+    if !package_directory.is_dir() {
+        return Err(Error::Io {
+            path: package_directory,
+            source: std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "path repository does not exist",
+            ),
+        });
+    }
+
+    let symlink = transport_options.as_ref().and_then(|o| o.symlink);
+    let relative = transport_options
+        .as_ref()
+        .and_then(|o| o.relative)
+        .unwrap_or(true);
+
+    // Same strategy as Composer: symlink by default and fall back to mirroring, unless forced by the options
+    let mirror_by_default = std::env::var("COMPOSER_MIRROR_PATH_REPOS")
+        .is_ok_and(|value| !value.is_empty() && value != "0");
+    let try_symlink = symlink.unwrap_or(!mirror_by_default);
+    let allow_mirror = symlink != Some(true);
+
+    remove_package_directory(&extract).await?;
+
+    let parent = extract.parent().unwrap();
+    tokio::fs::create_dir_all(parent)
+        .await
+        .map_err(Error::io(parent))?;
+
+    if try_symlink {
+        let package_directory = package_directory
+            .canonicalize()
+            .map_err(Error::io(&package_directory))?;
+        let target = if relative {
+            relative_path(
+                &parent.canonicalize().map_err(Error::io(parent))?,
+                &package_directory,
+            )
+        } else {
+            package_directory
+        };
+
+        match symlink_directory(&target, &extract) {
+            Ok(_) => return Ok(()),
+            Err(e) if !allow_mirror => return Err(Error::io(&extract)(e)),
+            Err(_) => {}
+        }
+    }
+
+    mirror_directory(&package_directory, &extract).map_err(Error::io(&extract))
+}
+
+/// Relative path from the `from` directory to `to`, both need to be canonical
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from = from.components().collect::<Vec<Component>>();
+    let to = to.components().collect::<Vec<Component>>();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+
+    path
+}
+
+#[cfg(unix)]
+fn symlink_directory(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink_directory(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(original, link)
+}
+
+fn mirror_directory(source: &Path, target: &Path) -> std::io::Result<()> {
+    for entry in walkdir::WalkDir::new(source).follow_links(true) {
+        let entry = entry?;
+        let target_path = target.join(entry.path().strip_prefix(source).unwrap());
+
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target_path)?;
+        } else {
+            std::fs::copy(entry.path(), &target_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Same directory naming as Composer uses for its vcs cache
+fn git_cache_key(url: &str) -> String {
+    url.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+async fn has_git_commit(repository: &Path, reference: &str) -> bool {
+    run_git(
+        &[
+            "rev-parse",
+            "--quiet",
+            "--verify",
+            format!("{}^{{commit}}", reference).as_str(),
+        ],
+        repository,
+    )
+    .await
+    .is_ok()
+}
+
+/// Paths are passed as they are, they do not have to be valid UTF-8
+async fn run_git<S: AsRef<OsStr>>(args: &[S], working_directory: &Path) -> Result<()> {
+    let command = || {
+        args.iter()
+            .map(|arg| arg.as_ref().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(working_directory)
+        .output()
+        .await
+        .map_err(|e| Error::Git {
+            command: command(),
+            message: e.to_string(),
+        })?;
+
+    if !output.status.success() {
+        return Err(Error::Git {
+            command: command(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the archive from the cache or downloads it, the cache stores the archive as downloaded
+async fn fetch_archive(
+    client: reqwest::Client,
+    source: &lock::ComposerPackageSource,
+    cache_file: PathBuf,
+    reporter: &Reporter,
+) -> Result<Vec<u8>> {
+    // Check if the file is already cached, using toktio
+    if cache_file.exists() {
+        let mut file = File::open(&cache_file)
+            .await
+            .map_err(Error::io(&cache_file))?;
+
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)
+            .await
+            .map_err(Error::io(&cache_file))?;
+
+        // A corrupted or tampered cache entry is evicted and downloaded again
+        if verify_checksum(source, &buffer).is_ok() {
+            return Ok(buffer);
+        }
+
+        reporter.report(Event::CachedArchiveMismatch {
+            url: source.url.clone(),
+        });
+
+        tokio::fs::remove_file(&cache_file)
+            .await
+            .map_err(Error::io(&cache_file))?;
+    }
+
+    let download_error = |e: reqwest::Error| Error::Download {
+        url: source.url.clone(),
+        source: e,
+    };
+
+    let resp = client
+        .get(source.url.as_str())
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(download_error)?;
+
+    let bytes = resp.bytes().await.map_err(download_error)?.to_vec();
+
+    verify_checksum(source, &bytes)?;
+
+    tokio::fs::write(&cache_file, &bytes)
+        .await
+        .map_err(Error::io(&cache_file))?;
+
+    Ok(bytes)
+}
+
+/// Compares the SHA-1 of an archive with the `shasum` of the lock, packages without a shasum are not verified
+fn verify_checksum(source: &lock::ComposerPackageSource, archive: &[u8]) -> Result<()> {
+    let expected = match &source.shasum {
+        Some(shasum) if !shasum.is_empty() => shasum.to_lowercase(),
+        _ => return Ok(()),
+    };
+
+    let actual = format!("{:x}", Sha1::digest(archive));
+
+    if actual != expected {
+        return Err(Error::ChecksumMismatch {
+            url: source.url.clone(),
+            expected,
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+/// Detects the compression of a tarball by its magic bytes, the dist type does not tell it reliably
+fn decompress_tar(archive: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+
+    if archive.starts_with(&[0x1f, 0x8b]) {
+        flate2::read::GzDecoder::new(archive.as_slice()).read_to_end(&mut decompressed)?;
+    } else if archive.starts_with(b"BZh") {
+        bzip2::read::BzDecoder::new(archive.as_slice()).read_to_end(&mut decompressed)?;
+    } else if archive.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        xz2::read::XzDecoder::new(archive.as_slice()).read_to_end(&mut decompressed)?;
+    } else {
+        return Ok(archive);
+    }
+
+    Ok(decompressed)
+}
+
+/// Extracts a tarball like zip_extract does, a single top-level directory is stripped
+fn extract_tar(archive: &[u8], extract: &Path) -> std::io::Result<()> {
+    let mut top_levels = vec![];
+    let mut has_nested_entries = false;
+
+    for entry in tar::Archive::new(archive).entries()? {
+        let entry = entry?;
+        if !is_extractable(&entry) {
+            continue;
+        }
+
+        let path = entry.path()?;
+        let mut components = path.components().filter(|c| *c != Component::CurDir);
+
+        if let Some(first) = components.next() {
+            if !top_levels.contains(&first.as_os_str().to_owned()) {
+                top_levels.push(first.as_os_str().to_owned());
+            }
+        }
+
+        has_nested_entries |= components.next().is_some();
+    }
+
+    let strip_toplevel = top_levels.len() == 1 && has_nested_entries;
+
+    std::fs::create_dir_all(extract)?;
+    let root = extract.canonicalize()?;
+
+    for entry in tar::Archive::new(archive).entries()? {
+        let mut entry = entry?;
+        if !is_extractable(&entry) {
+            continue;
+        }
+
+        let mut relative_path = PathBuf::new();
+
+        for component in entry.path()?.components() {
+            match component {
+                Component::Normal(part) => relative_path.push(part),
+                Component::CurDir => continue,
+                _ => return Err(outside_package_error(&entry.path()?)),
+            }
+        }
+
+        if strip_toplevel {
+            relative_path = relative_path.components().skip(1).collect();
+        }
+
+        if relative_path.as_os_str().is_empty() {
+            continue;
+        }
+
+        if let Some(link) = entry.link_name()? {
+            let base = match entry.header().entry_type() {
+                tar::EntryType::Symlink => relative_path.parent().unwrap_or(Path::new("")),
+                _ => Path::new(""),
+            };
+
+            if leaves_directory(&base.join(&link)) {
+                return Err(outside_package_error(&relative_path));
+            }
+        }
+
+        let target = extract.join(&relative_path);
+        if let Some(parent) = target.parent() {
+            // A symlink extracted before must not lead the entry out of the package
+            if !resolves_inside(parent, &root)? {
+                return Err(outside_package_error(&relative_path));
+            }
+
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // The tar crate resolves hard links against the working directory, they are relative to
+        // the archive root and created here
+        if entry.header().entry_type() == tar::EntryType::Link {
+            let source = hard_link_source(&entry, strip_toplevel)
+                .ok_or_else(|| outside_package_error(&relative_path))?;
+            let source = extract.join(source);
+
+            if !source
+                .parent()
+                .map_or(Ok(false), |parent| resolves_inside(parent, &root))?
+            {
+                return Err(outside_package_error(&relative_path));
+            }
+
+            if target.symlink_metadata().is_ok() {
+                std::fs::remove_file(&target)?;
+            }
+            std::fs::hard_link(&source, &target)?;
+            continue;
+        }
+
+        entry.unpack(&target)?;
+    }
+
+    Ok(())
+}
+
+/// The file a hard link entry points to relative to the package, none when it is outside of it
+fn hard_link_source<R: Read>(entry: &tar::Entry<R>, strip_toplevel: bool) -> Option<PathBuf> {
+    let link = entry.link_name().ok()??;
+    if leaves_directory(&link) {
+        return None;
+    }
+
+    let mut source = PathBuf::new();
+    for component in link.components() {
+        match component {
+            Component::Normal(part) => source.push(part),
+            Component::ParentDir => {
+                source.pop();
+            }
+            _ => {}
+        }
+    }
+
+    if strip_toplevel {
+        source = source.components().skip(1).collect();
+    }
+
+    (!source.as_os_str().is_empty()).then_some(source)
+}
+
+fn outside_package_error(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!(
+            "refusing to extract {} outside of the package directory",
+            path.display()
+        ),
+    )
+}
+
+/// Whether a relative path climbs above the directory it is relative to, without touching the disk
+fn leaves_directory(path: &Path) -> bool {
+    let mut depth = 0usize;
+
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return true,
+        }
+    }
+
+    false
+}
+
+/// Whether the deepest existing ancestor of a path is below the canonical root once symlinks are
+/// resolved
+fn resolves_inside(path: &Path, root: &Path) -> std::io::Result<bool> {
+    let mut existing = path;
+    while !existing.exists() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return Ok(false),
+        }
+    }
+
+    Ok(existing.canonicalize()?.starts_with(root))
+}
+
+fn is_extractable<R: Read>(entry: &tar::Entry<R>) -> bool {
+    !matches!(
+        entry.header().entry_type(),
+        tar::EntryType::XGlobalHeader | tar::EntryType::XHeader
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tar::EntryType::{Link, Regular, Symlink};
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("composer-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Entries with their content, or their target for symlinks and hard links
+    fn tarball(entries: &[(&str, tar::EntryType, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for (path, entry_type, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_entry_type(*entry_type);
+            match entry_type {
+                tar::EntryType::Symlink | tar::EntryType::Link => {
+                    header.set_size(0);
+                    builder.append_link(&mut header, path, data).unwrap();
+                }
+                _ => {
+                    header.set_size(data.len() as u64);
+                    builder
+                        .append_data(&mut header, path, data.as_bytes())
+                        .unwrap();
+                }
+            }
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn extract_tar_strips_the_top_level_directory() {
+        let directory = temp_directory("extract-tar");
+        let archive = tarball(&[
+            ("pkg/src/Foo.php", Regular, "<?php class Foo {}"),
+            ("pkg/link.php", Symlink, "src/Foo.php"),
+        ]);
+
+        extract_tar(&archive, &directory.join("vendor/foo")).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(directory.join("vendor/foo/link.php")).unwrap(),
+            "<?php class Foo {}"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn extract_tar_refuses_symlinks_leaving_the_package() {
+        let directory = temp_directory("extract-tar-symlink");
+        let outside = directory.join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+
+        let absolute = tarball(&[
+            ("pkg/evil", Symlink, outside.to_str().unwrap()),
+            ("pkg/evil/passwd", Regular, "pwned"),
+        ]);
+        assert!(extract_tar(&absolute, &directory.join("absolute")).is_err());
+
+        let relative = tarball(&[
+            ("pkg/evil", Symlink, "../.."),
+            ("pkg/evil/passwd", Regular, "pwned"),
+        ]);
+        assert!(extract_tar(&relative, &directory.join("relative")).is_err());
+
+        // Each link stays in the package on its own, together they lead out of it
+        let chained = tarball(&[
+            ("pkg/here", Symlink, "."),
+            ("pkg/here/up", Symlink, "../escape"),
+            ("pkg/here/up/passwd", Regular, "pwned"),
+        ]);
+        assert!(extract_tar(&chained, &directory.join("chained")).is_err());
+
+        assert!(!outside.join("passwd").exists());
+        assert!(!directory.join("escape").exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn extract_tar_refuses_hard_links_leaving_the_package() {
+        let directory = temp_directory("extract-tar-hard-link");
+        std::fs::write(directory.join("secret.txt"), "secret").unwrap();
+
+        for link in ["../secret.txt", "/etc/passwd", "pkg/../../secret.txt"] {
+            let archive = tarball(&[("pkg/a.txt", Regular, "a"), ("pkg/c.txt", Link, link)]);
+            assert!(
+                extract_tar(&archive, &directory.join("vendor/foo")).is_err(),
+                "{}",
+                link
+            );
+            assert!(!directory.join("vendor/foo/c.txt").exists(), "{}", link);
+        }
+
+        // Relative to the working directory like the tar crate would resolve it
+        let archive = tarball(&[
+            ("pkg/a.txt", Regular, "a"),
+            ("pkg/c.txt", Link, "Cargo.toml"),
+        ]);
+        assert!(extract_tar(&archive, &directory.join("cwd")).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn extract_tar_creates_hard_links_inside_the_package() {
+        let directory = temp_directory("extract-tar-hard-link-inside");
+        let archive = tarball(&[
+            ("pkg/a.txt", Regular, "a"),
+            ("pkg/sub/b.txt", Link, "pkg/a.txt"),
+        ]);
+
+        extract_tar(&archive, &directory.join("vendor/foo")).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(directory.join("vendor/foo/sub/b.txt")).unwrap(),
+            "a"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    fn git(args: &[&str], directory: &Path) -> String {
+        let output = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=composer-rs",
+                "-c",
+                "user.email=test@example.com",
+            ])
+            .args(args)
+            .current_dir(directory)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {}", args.join(" "));
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    fn dist(url: &str, archive: &[u8]) -> lock::ComposerPackageSource {
+        lock::ComposerPackageSource {
+            source_type: "zip".to_string(),
+            url: url.to_string(),
+            reference: "1234".to_string(),
+            shasum: Some(format!("{:x}", Sha1::digest(archive))),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_archive_uses_a_cached_archive_matching_its_checksum() {
+        let directory = temp_directory("fetch-cached");
+        let cache_file = directory.join("archive.zip");
+        std::fs::write(&cache_file, "cached").unwrap();
+
+        // The url cannot be downloaded, the archive has to come from the cache
+        let archive = fetch_archive(
+            reqwest::Client::new(),
+            &dist("not a url", b"cached"),
+            cache_file.clone(),
+            &Reporter::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(archive, b"cached");
+        assert!(cache_file.exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn fetch_archive_evicts_a_cached_archive_not_matching_its_checksum() {
+        let directory = temp_directory("fetch-mismatch");
+        let cache_file = directory.join("archive.zip");
+        std::fs::write(&cache_file, "tampered").unwrap();
+
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reporter = {
+            let events = events.clone();
+            Reporter::new(move |event| events.lock().unwrap().push(event.to_string()))
+        };
+
+        let result = fetch_archive(
+            reqwest::Client::new(),
+            &dist("not a url", b"cached"),
+            cache_file.clone(),
+            &reporter,
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::Download { .. })));
+        assert!(!cache_file.exists());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["Cached archive for not a url does not match its checksum, downloading again"]
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn install_package_from_git_checks_out_the_locked_commit() {
+        let directory = temp_directory("install-git");
+        let work = directory.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+
+        git(&["init", "--quiet"], &work);
+        std::fs::write(work.join("version.txt"), "1").unwrap();
+        git(&["add", "version.txt"], &work);
+        git(&["commit", "--quiet", "-m", "First"], &work);
+        let first = git(&["rev-parse", "HEAD"], &work);
+        std::fs::write(work.join("version.txt"), "2").unwrap();
+        git(&["commit", "--quiet", "-am", "Second"], &work);
+        git(
+            &["clone", "--quiet", "--bare", "work", "repository.git"],
+            &directory,
+        );
+
+        let url = format!("file://{}", directory.join("repository.git").display());
+        let options = InstallOptions {
+            working_directory: directory.clone(),
+            cache_directory: directory.join("cache"),
+            dev_mode: false,
+            prefer_source: true,
+            reporter: Reporter::default(),
+        };
+        let extract = directory.join("vendor/acme/foo");
+
+        install_package(
+            "acme/foo".to_string(),
+            reqwest::Client::new(),
+            lock::ComposerPackageSource {
+                source_type: "git".to_string(),
+                url: url.clone(),
+                reference: first.clone(),
+                shasum: None,
+            },
+            None,
+            options,
+            extract.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(extract.join("version.txt")).unwrap(),
+            "1"
+        );
+        assert_eq!(git(&["rev-parse", "HEAD"], &extract), first);
+        assert_eq!(git(&["remote", "get-url", "origin"], &extract), url);
+
+        let mirror = directory.join("cache/vcs").join(git_cache_key(&url));
+        assert!(has_git_commit(&mirror, &first).await);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+//! Installs the packages of a composer.lock and generates Composer's autoloader without PHP
+//!
+//! The `composer-rs` command line is a thin wrapper around these functions:
+//!
+//! ```no_run
+//! # async fn install() -> composer_rs::Result<()> {
+//! let working_directory = std::path::PathBuf::from("/srv/app");
+//! let lock = composer_rs::load_lock(&working_directory.join("composer.lock")).await?;
+//!
+//! let mut options = composer_rs::InstallOptions::default();
+//! options.working_directory = working_directory.clone();
+//! options.reporter = composer_rs::Reporter::new(|event| println!("{}", event));
+//! composer_rs::install_packages(&lock, &options).await?;
+//! let autoload_options = composer_rs::AutoloadOptions {
+//!     dev_mode: Some(false),
+//!     ..Default::default()
+//! };
+//! composer_rs::generate_autoload(&lock, &working_directory, &autoload_options).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod autoload;
+mod binaries;
+mod classmap;
+mod composer_json;
+mod error;
+mod installed;
+mod installer;
+mod lock;
+mod php_scanner;
+mod report;
+mod version;
+
+use std::path::{Path, PathBuf};
+
+pub use autoload::AutoloadOptions;
+pub use classmap::ScanMode;
+pub use error::{Error, Result};
+pub use lock::{
+    ComposerAutoload, ComposerLock, ComposerLockAlias, ComposerPackage, ComposerPackageSource,
+    ComposerTransportOptions,
+};
+pub use report::{Event, Reporter};
+
+/// Where and how the packages of a lock are installed, new options may be added so it is built
+/// from its [`Default`]
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct InstallOptions {
+    /// The project directory with composer.json, packages are installed into its vendor directory,
+    /// the current directory by default
+    pub working_directory: PathBuf,
+    /// Downloaded archives and git mirrors are kept here, composer-rs in the temporary directory by
+    /// default
+    pub cache_directory: PathBuf,
+    /// Also install the packages of `packages-dev`
+    pub dev_mode: bool,
+    /// Install packages from their source repository instead of the dist archive
+    pub prefer_source: bool,
+    /// Receives the installed, updated and removed packages and skipped binaries
+    pub reporter: Reporter,
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        InstallOptions {
+            working_directory: PathBuf::from("."),
+            cache_directory: std::env::temp_dir().join("composer-rs"),
+            dev_mode: false,
+            prefer_source: false,
+            reporter: Reporter::default(),
+        }
+    }
+}
+
+/// Loads a composer.lock
+pub async fn load_lock(path: &Path) -> Result<ComposerLock> {
+    lock::load_composer_lock(path.to_path_buf()).await
+}
+
+/// Installs, updates and removes packages until the vendor directory matches the lock, together
+/// with their binaries and installed.json, the autoloader is generated by [`generate_autoload`]
+pub async fn install_packages(
+    composer_lock: &ComposerLock,
+    options: &InstallOptions,
+) -> Result<()> {
+    let working_directory = &options.working_directory;
+    let reporter = &options.reporter;
+
+    let cache_archive_directory = options.cache_directory.join("archives");
+
+    tokio::fs::create_dir_all(&cache_archive_directory)
+        .await
+        .map_err(Error::io(&cache_archive_directory))?;
+
+    let mut handles: Vec<(String, tokio::task::JoinHandle<Result<()>>)> = Vec::new();
+
+    let client = reqwest::Client::builder()
+        .user_agent("composer-rs")
+        .build()
+        .map_err(Error::HttpClient)?;
+
+    let composer_json =
+        composer_json::load_composer_json(working_directory.join("composer.json")).await?;
+
+    let vendor_directory = working_directory.join("vendor");
+    let packages = composer_lock.installable_packages(options.dev_mode);
+    let installed_packages = installed::load_installed_packages(&vendor_directory).await?;
+
+    let operations =
+        installed::compute_operations(&installed_packages, &packages, &vendor_directory);
+
+    if operations.is_empty() {
+        reporter.report(Event::NothingToInstall);
+    }
+
+    // Like Composer all packages are removed before any is installed, removing the last package of
+    // a vendor also removes its directory which must not race with an installation into it
+    let mut installs = vec![];
+
+    for operation in operations {
+        match operation {
+            installed::Operation::Install(package) => {
+                reporter.report(Event::Installing {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                });
+                installs.push(package);
+            }
+            installed::Operation::Update(from, to) => {
+                reporter.report(Event::Updating {
+                    name: to.name.clone(),
+                    from: from.version.clone(),
+                    to: to.version.clone(),
+                });
+                binaries::remove_binaries(&vendor_directory, &from).await?;
+                installer::remove_package(&vendor_directory.join(&to.name)).await?;
+                installs.push(to);
+            }
+            installed::Operation::Uninstall(package) => {
+                reporter.report(Event::Removing {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                });
+                binaries::remove_binaries(&vendor_directory, &package).await?;
+                installer::remove_package(&vendor_directory.join(&package.name)).await?;
+            }
+        }
+    }
+
+    for package in installs {
+        // Skip meta-packages, these are only virtual and should not be installed
+        if installed::is_metapackage(&package) {
+            continue;
+        }
+
+        let install_directory = vendor_directory.join(package.install_path());
+        let source = match package.installation_source(options.prefer_source) {
+            Some("source") => package.source.unwrap(),
+            Some(_) => package.dist.unwrap(),
+            None => return Err(Error::package(&package.name, "no source or dist specified")),
+        };
+
+        let handle = tokio::spawn(installer::install_package(
+            package.name.clone(),
+            client.clone(),
+            source,
+            package.transport_options,
+            options.clone(),
+            install_directory,
+        ));
+        handles.push((package.name, handle));
+    }
+
+    for (name, handle) in handles {
+        match handle.await {
+            Ok(result) => result?,
+            Err(e) => return Err(Error::package(&name, format!("installation failed: {}", e))),
+        }
+    }
+
+    binaries::install_binaries(
+        &vendor_directory,
+        &packages,
+        composer_json
+            .config()
+            .bin_compat
+            .as_deref()
+            .unwrap_or("auto"),
+        reporter,
+    )
+    .await?;
+
+    installed::write_installed_packages(
+        &vendor_directory,
+        &packages,
+        &composer_lock.dev_package_names(),
+        options.dev_mode,
+        options.prefer_source,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Generates vendor/autoload.php and vendor/composer for the packages of the lock
+pub async fn generate_autoload(
+    composer_lock: &ComposerLock,
+    working_directory: &Path,
+    options: &AutoloadOptions,
+) -> Result<()> {
+    let composer_json =
+        composer_json::load_composer_json(working_directory.join("composer.json")).await?;
+
+    let vendor_directory = working_directory.join("vendor");
+
+    let dev_mode = match options.dev_mode {
+        Some(dev_mode) => dev_mode,
+        None => installed::load_installed_dev_mode(&vendor_directory)
+            .await
+            .unwrap_or(true),
+    };
+
+    autoload::generate_composer_autoload(
+        composer_lock.clone(),
+        composer_json,
+        vendor_directory,
+        dev_mode,
+        options.clone(),
+    )
+    .await
+}
+
+/// Classes declared in a PHP file or the files below a directory, with the file declaring them,
+/// files which cannot be parsed are reported
+pub async fn scan_classmap(
+    path: &Path,
+    scan_mode: ScanMode,
+    reporter: &Reporter,
+) -> Result<Vec<(String, PathBuf)>> {
+    let (directory, relative_path) = match path.is_file() {
+        true => (
+            path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            path.file_name().unwrap().to_string_lossy().to_string(),
+        ),
+        false => (path.to_path_buf(), String::new()),
+    };
+
+    let classes = classmap::generate_classmap(
+        directory.clone(),
+        relative_path,
+        &classmap::ExcludePatterns::default(),
+        scan_mode,
+        reporter,
+    )
+    .await?;
+
+    Ok(classes
+        .into_iter()
+        .map(|(class, file)| (class, directory.join(file)))
+        .collect())
+}
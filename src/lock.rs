@@ -4,7 +4,8 @@ use std::path::PathBuf;
 use serde::Deserialize;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
-use anyhow::Result;
+
+use crate::error::{Error, Result};
 
 #[derive(Deserialize, Clone)]
 pub struct ComposerPackageSource {
@@ -12,21 +13,64 @@ pub struct ComposerPackageSource {
     pub source_type: String,
     pub url: String,
     pub reference: String,
+    pub shasum: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ComposerTransportOptions {
+    pub symlink: Option<bool>,
+    pub relative: Option<bool>,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct ComposerAutoload {
     pub files: Option<Vec<String>>,
-    #[serde(alias = "psr-0")]
-    pub psr0: Option<HashMap<String, String>>,
-    #[serde(alias = "psr-4")]
-    pub psr4: Option<HashMap<String, String>>,
+    #[serde(
+        alias = "psr-0",
+        default,
+        deserialize_with = "deserialize_autoload_paths"
+    )]
+    pub psr0: Option<HashMap<String, Vec<String>>>,
+    #[serde(
+        alias = "psr-4",
+        default,
+        deserialize_with = "deserialize_autoload_paths"
+    )]
+    pub psr4: Option<HashMap<String, Vec<String>>>,
     #[serde(alias = "classmap")]
     pub class_map: Option<Vec<String>>,
     #[serde(alias = "exclude-from-classmap")]
     pub exclude_from_class_map: Option<Vec<String>>,
 }
 
+/// PSR-0 and PSR-4 namespaces map to a single path or a list of paths
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ComposerAutoloadPaths {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+fn deserialize_autoload_paths<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<HashMap<String, Vec<String>>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mappings: Option<HashMap<String, ComposerAutoloadPaths>> =
+        Option::deserialize(deserializer)?;
+
+    Ok(mappings.map(|mappings| {
+        mappings
+            .into_iter()
+            .map(|(namespace, paths)| match paths {
+                ComposerAutoloadPaths::Single(path) => (namespace, vec![path]),
+                ComposerAutoloadPaths::Multiple(paths) => (namespace, paths),
+            })
+            .collect()
+    }))
+}
+
 #[derive(Deserialize, Clone)]
 pub struct ComposerPackage {
     pub name: String,
@@ -35,28 +79,308 @@ pub struct ComposerPackage {
     pub dist: Option<ComposerPackageSource>,
     #[serde(alias = "type")]
     pub package_type: Option<String>,
-    pub autoload: Option<ComposerAutoload>
+    pub autoload: Option<ComposerAutoload>,
+    #[serde(alias = "target-dir")]
+    pub target_dir: Option<String>,
+    #[serde(alias = "include-path")]
+    pub include_path: Option<Vec<String>>,
+    pub require: Option<HashMap<String, String>>,
+    pub bin: Option<Vec<String>>,
+    pub replace: Option<HashMap<String, String>>,
+    pub provide: Option<HashMap<String, String>>,
+    #[serde(alias = "transport-options")]
+    pub transport_options: Option<ComposerTransportOptions>,
+    /// The package exactly as written in the lock, including fields not modeled above
+    #[serde(skip)]
+    pub json: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ComposerPackage {
+    /// Where the package is installed relative to vendor/, packages with a `target-dir` are installed below it
+    pub fn install_path(&self) -> String {
+        match self.target_dir.as_deref().map(|dir| dir.trim_matches('/')) {
+            Some(target_dir) if !target_dir.is_empty() => format!("{}/{}", self.name, target_dir),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// Whether the package gets installed from its `source` or `dist`, the dist is used unless the source is preferred or the only one
+    pub fn installation_source(&self, prefer_source: bool) -> Option<&'static str> {
+        match (&self.source, &self.dist) {
+            (Some(_), _) if prefer_source => Some("source"),
+            (_, Some(_)) => Some("dist"),
+            (Some(_), None) => Some("source"),
+            (None, None) => None,
+        }
+    }
+
+    /// Versions this package is also known as, from the lock's root aliases and its own `branch-alias`
+    pub fn aliases(&self, lock_aliases: &[ComposerLockAlias]) -> Vec<String> {
+        let mut aliases = lock_aliases
+            .iter()
+            .filter(|alias| alias.package == self.name && alias.version == self.version)
+            .map(|alias| alias.alias.clone())
+            .collect::<Vec<String>>();
+
+        let branch_alias = self
+            .json
+            .get("extra")
+            .and_then(|extra| extra.get("branch-alias"))
+            .and_then(|branch_alias| branch_alias.get(&self.version))
+            .and_then(|alias| alias.as_str());
+
+        if let Some(alias) = branch_alias {
+            if self.version.starts_with("dev-") && !aliases.contains(&alias.to_string()) {
+                aliases.push(alias.to_string());
+            }
+        }
+
+        aliases
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ComposerLockAlias {
+    pub package: String,
+    pub version: String,
+    pub alias: String,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct ComposerLock {
     pub packages: Vec<ComposerPackage>,
+    #[serde(alias = "packages-dev")]
+    pub packages_dev: Option<Vec<ComposerPackage>>,
     #[serde(alias = "content-hash")]
     pub content_hash: String,
+    pub aliases: Option<Vec<ComposerLockAlias>>,
+}
+
+impl ComposerLock {
+    /// Returns all packages of the lock, `packages-dev` are only included in dev mode
+    pub fn installable_packages(&self, dev_mode: bool) -> Vec<ComposerPackage> {
+        let mut packages = self.packages.clone();
+
+        if dev_mode {
+            packages.extend(self.packages_dev.clone().unwrap_or_default());
+        }
+
+        packages
+    }
+
+    pub fn dev_package_names(&self) -> Vec<String> {
+        self.packages_dev
+            .iter()
+            .flatten()
+            .map(|package| package.name.clone())
+            .collect()
+    }
+}
+
+/// Sorts packages so dependencies come before the packages requiring them, like Composer's PackageSorter
+///
+/// Every package requiring a package lowers its weight, packages with the same weight are sorted by name
+pub fn sort_packages(packages: Vec<ComposerPackage>) -> Vec<ComposerPackage> {
+    let mut usages: HashMap<String, Vec<String>> = HashMap::new();
+    for package in &packages {
+        for target in package.require.iter().flatten().map(|(target, _)| target) {
+            usages
+                .entry(target.to_lowercase())
+                .or_default()
+                .push(package.name.clone());
+        }
+    }
+
+    let mut weights: HashMap<String, i64> = HashMap::new();
+    let mut weighted = packages
+        .into_iter()
+        .map(|package| {
+            let weight = package_weight(&package.name, &usages, &mut weights, &mut vec![]);
+            (weight, package)
+        })
+        .collect::<Vec<(i64, ComposerPackage)>>();
+
+    weighted.sort_by(|(a_weight, a), (b_weight, b)| {
+        a_weight
+            .cmp(b_weight)
+            .then_with(|| natural_cmp(&a.name, &b.name))
+    });
+
+    weighted.into_iter().map(|(_, package)| package).collect()
+}
+
+fn package_weight(
+    name: &str,
+    usages: &HashMap<String, Vec<String>>,
+    weights: &mut HashMap<String, i64>,
+    computing: &mut Vec<String>,
+) -> i64 {
+    if let Some(weight) = weights.get(name) {
+        return *weight;
+    }
+
+    // Circular dependencies do not add any weight
+    if computing.iter().any(|computing| computing == name) {
+        return 0;
+    }
+
+    computing.push(name.to_string());
+
+    let mut weight = 0;
+    for user in usages.get(&name.to_lowercase()).into_iter().flatten() {
+        weight -= 1 - package_weight(user, usages, weights, computing);
+    }
+
+    computing.pop();
+    weights.insert(name.to_string(), weight);
+
+    weight
+}
+
+/// Case insensitive comparison where digits are compared by their value, like PHP's strnatcasecmp
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut number = String::new();
+                    while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+                        number.push(digit);
+                    }
+                    number.trim_start_matches('0').to_string()
+                };
+                let (a_number, b_number) = (take_number(&mut a), take_number(&mut b));
+
+                let ordering = a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(&b_number));
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            (Some(a_char), Some(b_char)) => {
+                let ordering = a_char
+                    .to_ascii_lowercase()
+                    .cmp(&b_char.to_ascii_lowercase());
+                if ordering.is_ne() {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
 }
 
 pub async fn load_composer_lock(file_path: PathBuf) -> Result<ComposerLock> {
-    let mut composer_lock = File::open(file_path)
+    let mut composer_lock = File::open(&file_path)
         .await
-        .expect("failed to open composer.lock");
+        .map_err(Error::io(&file_path))?;
 
     let mut buffer = Vec::new();
     composer_lock
         .read_to_end(&mut buffer)
         .await
-        .expect("failed to read composer.lock");
+        .map_err(Error::io(&file_path))?;
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&buffer).map_err(Error::json(&file_path))?;
+    let mut parsed: ComposerLock =
+        serde_json::from_value(json.clone()).map_err(Error::json(&file_path))?;
 
-    let parsed = serde_json::from_slice(&buffer).expect("failed to parse composer.lock");
+    attach_package_json(&mut parsed.packages, &json["packages"]);
+    if let Some(packages_dev) = parsed.packages_dev.as_mut() {
+        attach_package_json(packages_dev, &json["packages-dev"]);
+    }
 
     Ok(parsed)
+}
+
+fn attach_package_json(packages: &mut [ComposerPackage], json: &serde_json::Value) {
+    if let Some(entries) = json.as_array() {
+        for (package, entry) in packages.iter_mut().zip(entries) {
+            package.json = entry.as_object().cloned().unwrap_or_default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    fn package(name: &str, requires: &[&str]) -> ComposerPackage {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": "1.0.0",
+            "require": requires.iter().map(|target| (*target, "*")).collect::<HashMap<_, _>>(),
+        }))
+        .unwrap()
+    }
+
+    fn sorted_names(packages: Vec<ComposerPackage>) -> Vec<String> {
+        sort_packages(packages)
+            .into_iter()
+            .map(|package| package.name)
+            .collect()
+    }
+
+    #[test]
+    fn sort_packages_installs_dependencies_first() {
+        let packages = vec![
+            package("vendor/a", &["vendor/b"]),
+            package("vendor/b", &["vendor/c", "php"]),
+            package("vendor/c", &[]),
+        ];
+
+        assert_eq!(
+            sorted_names(packages),
+            vec!["vendor/c", "vendor/b", "vendor/a"]
+        );
+    }
+
+    #[test]
+    fn sort_packages_handles_cycles() {
+        let packages = vec![
+            package("vendor/app", &["vendor/x"]),
+            package("vendor/x", &["vendor/y"]),
+            package("vendor/y", &["vendor/x"]),
+        ];
+
+        assert_eq!(
+            sorted_names(packages),
+            vec!["vendor/x", "vendor/y", "vendor/app"]
+        );
+    }
+
+    #[test]
+    fn sort_packages_orders_ties_naturally() {
+        let packages = vec![
+            package("vendor/pkg10", &[]),
+            package("vendor/pkg2", &[]),
+            package("Vendor/Pkg1", &[]),
+        ];
+
+        assert_eq!(
+            sorted_names(packages),
+            vec!["Vendor/Pkg1", "vendor/pkg2", "vendor/pkg10"]
+        );
+    }
+
+    #[test]
+    fn natural_cmp_compares_numbers_by_value() {
+        assert_eq!(natural_cmp("pkg2", "pkg10"), Ordering::Less);
+        assert_eq!(natural_cmp("pkg10", "pkg2"), Ordering::Greater);
+        assert_eq!(natural_cmp("pkg002", "pkg2"), Ordering::Equal);
+        assert_eq!(natural_cmp("Foo", "foo"), Ordering::Equal);
+        assert_eq!(natural_cmp("foo", "foo-bar"), Ordering::Less);
+        assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+    }
 }
\ No newline at end of file
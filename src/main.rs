@@ -1,15 +1,7 @@
-use anyhow::anyhow;
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::{
-    io::Cursor,
-    path::{Path, PathBuf},
-};
-use tokio::{fs::File, io::AsyncReadExt};
-
-mod autoload;
-mod lock;
-mod classmap;
+use clap::{Args, Parser, Subcommand};
+use composer_rs::{AutoloadOptions, InstallOptions, Reporter, ScanMode};
+use std::path::Path;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -27,10 +19,94 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    Install {},
+    Install {
+        /// Skip installing and autoloading packages listed in packages-dev
+        #[clap(long)]
+        no_dev: bool,
+        /// Install packages from their source repository instead of the dist archive
+        #[clap(long)]
+        prefer_source: bool,
+        #[clap(flatten)]
+        autoload: AutoloadArgs,
+    },
+    /// Regenerate the autoloader from composer.lock and the installed vendor directory
+    DumpAutoload {
+        /// Use the autoload-dev rules, defaults to the mode of the last install
+        #[clap(long, conflicts_with = "no_dev")]
+        dev: bool,
+        /// Skip the autoload-dev rules
+        #[clap(long)]
+        no_dev: bool,
+        #[clap(flatten)]
+        autoload: AutoloadArgs,
+    },
     ClearCache {},
 }
 
+#[derive(Args)]
+struct AutoloadArgs {
+    /// Convert PSR-0 and PSR-4 autoloading into the classmap for a faster autoloader
+    #[clap(long, short, alias = "optimize-autoloader")]
+    optimize: bool,
+    /// Only autoload classes from the classmap, implies --optimize
+    #[clap(long, short = 'a')]
+    classmap_authoritative: bool,
+    /// Cache found and missing classes in APCu
+    #[clap(long, alias = "apcu-autoloader")]
+    apcu: bool,
+    /// Prefix of the APCu cache, implies --apcu
+    #[clap(long, alias = "apcu-autoloader-prefix")]
+    apcu_prefix: Option<String>,
+    /// Scan classmap directories with the full PHP parser instead of the faster, more tolerant scanner
+    #[clap(long)]
+    classmap_parser: bool,
+    /// Fail when the same class is declared in more than one file
+    #[clap(long)]
+    strict_ambiguous: bool,
+    /// Fail when a PSR-0 or PSR-4 directory contains classes which do not comply with the standard
+    #[clap(long)]
+    strict_psr: bool,
+}
+
+impl AutoloadArgs {
+    fn options(&self) -> AutoloadOptions {
+        // Like Composer a random prefix is used unless one is given
+        let apcu_prefix = match (&self.apcu_prefix, self.apcu) {
+            (Some(prefix), _) => Some(prefix.clone()),
+            (None, true) => Some(format!(
+                "{:016x}",
+                xxhash_rust::xxh3::xxh3_64(
+                    &std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_nanos()
+                        .to_le_bytes()
+                )
+            )),
+            (None, false) => None,
+        };
+
+        AutoloadOptions {
+            dev_mode: None,
+            scan_mode: match self.classmap_parser {
+                true => ScanMode::Parser,
+                false => ScanMode::Cleaner,
+            },
+            strict_ambiguous: self.strict_ambiguous,
+            strict_psr: self.strict_psr,
+            optimize: self.optimize,
+            classmap_authoritative: self.classmap_authoritative,
+            apcu_prefix,
+            reporter: console_reporter(),
+        }
+    }
+}
+
+/// Prints the progress and warnings of the library
+fn console_reporter() -> Reporter {
+    Reporter::new(|event| println!("{}", event))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let mut cli = Cli::parse();
@@ -57,7 +133,11 @@ async fn main() -> Result<()> {
     }
 
     match &cli.command {
-        Some(Commands::Install {}) => {
+        Some(Commands::Install {
+            no_dev,
+            prefer_source,
+            autoload,
+        }) => {
             let project_root = cli.working_directory.unwrap();
             let project_root_str = &project_root.as_str();
             let cache_directory = cli.cache_directory.unwrap();
@@ -65,6 +145,28 @@ async fn main() -> Result<()> {
             return install_from_composer_lock(
                 Path::new(&project_root_str),
                 Path::new(cache_directory_str),
+                !no_dev,
+                *prefer_source,
+                autoload.options(),
+            )
+            .await;
+        }
+        Some(Commands::DumpAutoload {
+            dev,
+            no_dev,
+            autoload,
+        }) => {
+            let project_root = cli.working_directory.unwrap();
+            return dump_autoload(
+                Path::new(&project_root),
+                AutoloadOptions {
+                    dev_mode: match (dev, no_dev) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    },
+                    ..autoload.options()
+                },
             )
             .await;
         }
@@ -85,130 +187,45 @@ async fn main() -> Result<()> {
 async fn install_from_composer_lock(
     working_directory: &Path,
     cache_directory: &Path,
+    dev_mode: bool,
+    prefer_source: bool,
+    autoload_options: AutoloadOptions,
 ) -> Result<()> {
-    let composer_lock =
-        lock::load_composer_lock(working_directory.join(Path::new("composer.lock")))
-            .await
-            .expect("failed to load composer.lock");
-
-    let cache_archive_directory = cache_directory.join("archives");
-
-    tokio::fs::create_dir_all(cache_archive_directory)
-        .await
-        .expect("failed to create cache directory");
-
-    let mut handles: Vec<tokio::task::JoinHandle<std::prelude::v1::Result<(), anyhow::Error>>> =
-        Vec::new();
-
-    let client = reqwest::Client::builder()
-        .user_agent("composer-rs")
-        .build()
-        .unwrap();
-
-    for package in composer_lock.clone().packages {
-        // Skip meta-packages, these are only virtual and should not be installed
-        if package
-            .package_type
-            .is_some_and(|package_type| package_type == "metapackage")
-        {
-            continue;
-        }
-
-        println!("Installing {} in version {}", package.name, package.version);
-
-        if package.source.is_none() && package.dist.is_none() {
-            return Err(anyhow!(
-                "Package {} has no source or dist specified",
-                package.name
-            ));
-        }
-
-        let handle = tokio::spawn(install_package(
-            client.clone(),
-            package.dist.unwrap_or_else(|| package.source.unwrap()),
-            cache_directory.join(Path::new("archives")),
-            working_directory
-                .join(Path::new("vendor"))
-                .join(Path::new(package.name.as_str())),
-        ));
-        handles.push(handle);
-    }
-
-    let results = futures::future::join_all(handles).await;
-
-    for result in results {
-        match result {
-            Ok(Ok(_)) => continue,
-            Ok(Err(e)) => return Err(anyhow!("Future failed with error: {}", e)),
-            Err(e) => return Err(anyhow!("Future panicked: {}", e)),
-        }
-    }
-
-    autoload::generate_composer_autoload(composer_lock, working_directory.join(Path::new("vendor")))
-        .await
-        .expect("Failed to generate composer autoload");
+    let composer_lock = composer_rs::load_lock(&working_directory.join("composer.lock")).await?;
+
+    let mut install_options = InstallOptions::default();
+    install_options.working_directory = working_directory.to_path_buf();
+    install_options.cache_directory = cache_directory.to_path_buf();
+    install_options.dev_mode = dev_mode;
+    install_options.prefer_source = prefer_source;
+    install_options.reporter = console_reporter();
+
+    composer_rs::install_packages(&composer_lock, &install_options).await?;
+
+    composer_rs::generate_autoload(
+        &composer_lock,
+        working_directory,
+        &AutoloadOptions {
+            dev_mode: Some(dev_mode),
+            ..autoload_options
+        },
+    )
+    .await?;
 
     Ok(())
 }
 
-async fn install_package(
-    client: reqwest::Client,
-    source: lock::ComposerPackageSource,
-    cache_directory: PathBuf,
-    extract: PathBuf,
-) -> Result<()> {
-    match source.source_type.as_str() {
-        "zip" => install_package_from_zip(client, source, cache_directory, extract).await,
-        source_type => Err(anyhow!("Unsupported source type: {}", source_type)),
-    }
-}
+/// Without --dev or --no-dev the mode of the last install is kept
+async fn dump_autoload(working_directory: &Path, autoload_options: AutoloadOptions) -> Result<()> {
+    let composer_lock = composer_rs::load_lock(&working_directory.join("composer.lock")).await?;
 
-async fn install_package_from_zip(
-    client: reqwest::Client,
-    source: lock::ComposerPackageSource,
-    cache_directory: PathBuf,
-    extract: PathBuf,
-) -> Result<()> {
-    let cache_file = cache_directory.join(Path::new(format!("{}.zip", source.reference).as_str()));
-
-    // Check if the file is already cached, using toktio
-    if cache_file.exists() {
-        let mut file = File::open(&cache_file)
-            .await
-            .expect("failed to open cached file");
-
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)
-            .await
-            .expect("failed to read cached file");
-
-        tokio::fs::create_dir_all(&extract)
-            .await
-            .expect("failed to create directory");
-
-        zip_extract::extract(Cursor::new(buffer.clone()), &extract, true)
-            .expect("failed to extract zip file");
-    } else {
-        let resp = client
-            .get(source.url)
-            .send()
-            .await
-            .expect("failed to download package")
-            .error_for_status()
-            .expect("invalid repsonse from server");
-
-        let bytes = resp.bytes().await.expect("failed to read response body");
-
-        tokio::fs::create_dir_all(&extract)
-            .await
-            .expect("failed to create directory");
-
-        zip_extract::extract(Cursor::new(bytes.clone()), &extract, true)
-            .expect("failed to extract zip file");
-
-        tokio::fs::write(&cache_file, bytes)
-            .await
-            .expect("failed to write file");
+    println!("Generating autoload files");
+
+    composer_rs::generate_autoload(&composer_lock, working_directory, &autoload_options).await?;
+
+    match autoload_options.optimize() {
+        true => println!("Generated optimized autoload files"),
+        false => println!("Generated autoload files"),
     }
 
     Ok(())
@@ -0,0 +1,388 @@
+/// Finds the classes, interfaces, traits and enums declared in a PHP file like Composer's PhpFileParser.
+///
+/// Strings, comments, heredocs and inline HTML are skipped, then declarations and namespaces are
+/// matched on what remains. Unlike a full parser this works on any PHP version and on invalid code.
+pub fn find_classes(contents: &[u8]) -> Vec<String> {
+    let mut scanner = Scanner { contents, index: 0 };
+    let mut classes = vec![];
+    let mut namespace = String::new();
+
+    while scanner.skip_to_php() {
+        while scanner.index < contents.len() {
+            let char = contents[scanner.index];
+
+            match char {
+                b'?' if scanner.peek(1) == Some(b'>') => {
+                    scanner.index += 2;
+                    break;
+                }
+                b'\'' | b'"' | b'`' => scanner.skip_string(char),
+                b'#' if scanner.peek(1) != Some(b'[') => scanner.skip_line_comment(),
+                b'/' if scanner.peek(1) == Some(b'/') => scanner.skip_line_comment(),
+                b'/' if scanner.peek(1) == Some(b'*') => scanner.skip_block_comment(),
+                b'<' if contents[scanner.index..].starts_with(b"<<<") => scanner.skip_heredoc(),
+                char if is_identifier_start(char) => {
+                    // `$class`, `::class` and `->class` are not declarations
+                    let previous = scanner.index.checked_sub(1).map(|index| contents[index]);
+                    let word = scanner.read_identifier();
+
+                    if matches!(previous, Some(b'$' | b':' | b'>')) {
+                        continue;
+                    }
+
+                    let keyword = word.to_ascii_lowercase();
+                    match keyword.as_slice() {
+                        b"class" | b"interface" | b"trait" | b"enum" => {
+                            if let Some(name) = scanner.read_declaration_name() {
+                                if let Some(class) = class_name(&keyword, &name) {
+                                    classes.push(format!("{}{}", namespace, class));
+                                }
+                            }
+                        }
+                        b"namespace" => {
+                            if let Some(name) = scanner.read_namespace_name() {
+                                namespace = match name.is_empty() {
+                                    true => String::new(),
+                                    false => format!("{}\\", name),
+                                };
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => scanner.index += 1,
+            }
+        }
+    }
+
+    classes
+}
+
+/// The class name of a matched declaration, anonymous classes have none
+fn class_name(keyword: &[u8], name: &str) -> Option<String> {
+    if name == "extends" || name == "implements" {
+        return None;
+    }
+
+    // XHP classes, e.g. `class :foo:bar-baz` is `xhp_foo__bar_baz`
+    if let Some(xhp) = name.strip_prefix(':') {
+        return Some(format!("xhp_{}", xhp.replace('-', "_").replace(':', "__")));
+    }
+
+    // The backing type of an enum is matched as well, e.g. `enum Suit: string`
+    if keyword == b"enum" {
+        if let Some((name, _)) = name.rsplit_once(':') {
+            return Some(name.to_string());
+        }
+    }
+
+    Some(name.to_string())
+}
+
+struct Scanner<'a> {
+    contents: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.contents.get(self.index + offset).copied()
+    }
+
+    fn skip_while(&mut self, predicate: impl Fn(u8) -> bool) {
+        while self.index < self.contents.len() && predicate(self.contents[self.index]) {
+            self.index += 1;
+        }
+    }
+
+    fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.contents[self.index..]
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|position| self.index + position)
+    }
+
+    /// Moves past the next opening tag, everything before it is inline HTML
+    fn skip_to_php(&mut self) -> bool {
+        let Some(position) = self.find(b"<?") else {
+            self.index = self.contents.len();
+            return false;
+        };
+
+        self.index = position + 2;
+        if self.contents[self.index..]
+            .get(..3)
+            .is_some_and(|tag| tag.eq_ignore_ascii_case(b"php"))
+        {
+            self.index += 3;
+        }
+
+        true
+    }
+
+    fn skip_string(&mut self, quote: u8) {
+        self.index += 1;
+        while self.index < self.contents.len() {
+            match self.contents[self.index] {
+                b'\\' => self.index += 2,
+                char if char == quote => {
+                    self.index += 1;
+                    return;
+                }
+                _ => self.index += 1,
+            }
+        }
+        self.index = self.index.min(self.contents.len());
+    }
+
+    /// Line comments end at a newline or a closing tag
+    fn skip_line_comment(&mut self) {
+        while self.index < self.contents.len() {
+            match self.contents[self.index] {
+                b'\n' | b'\r' => return,
+                b'?' if self.peek(1) == Some(b'>') => return,
+                _ => self.index += 1,
+            }
+        }
+    }
+
+    fn skip_block_comment(&mut self) {
+        self.index += 2;
+        self.index = self
+            .find(b"*/")
+            .map_or(self.contents.len(), |position| position + 2);
+    }
+
+    /// Heredocs and nowdocs, the closing identifier may be indented since PHP 7.3
+    fn skip_heredoc(&mut self) {
+        let start = self.index;
+        self.index += 3;
+        self.skip_while(|char| char == b' ' || char == b'\t');
+
+        let quote = match self.peek(0) {
+            Some(quote @ (b'\'' | b'"')) => {
+                self.index += 1;
+                Some(quote)
+            }
+            _ => None,
+        };
+
+        let identifier = match self.peek(0) {
+            Some(char) if is_identifier_start(char) => self.read_identifier().to_vec(),
+            _ => {
+                self.index = start + 1;
+                return;
+            }
+        };
+
+        if let Some(quote) = quote {
+            if self.peek(0) != Some(quote) {
+                self.index = start + 1;
+                return;
+            }
+            self.index += 1;
+        }
+
+        if !matches!(self.peek(0), Some(b'\n' | b'\r')) {
+            self.index = start + 1;
+            return;
+        }
+
+        while self.index < self.contents.len() {
+            let char = self.contents[self.index];
+            self.index += 1;
+
+            if char != b'\n' && char != b'\r' {
+                continue;
+            }
+
+            self.skip_while(|char| char == b' ' || char == b'\t');
+            if self.contents[self.index..].starts_with(&identifier)
+                && !self
+                    .contents
+                    .get(self.index + identifier.len())
+                    .is_some_and(|char| is_identifier_char(*char))
+            {
+                self.index += identifier.len();
+                return;
+            }
+        }
+    }
+
+    fn read_identifier(&mut self) -> &'a [u8] {
+        let start = self.index;
+        self.skip_while(is_identifier_char);
+        &self.contents[start..self.index]
+    }
+
+    /// The name after `class`, `interface`, `trait` or `enum`, which must be separated by whitespace
+    fn read_declaration_name(&mut self) -> Option<String> {
+        let start = self.index;
+        self.skip_while(|char| char.is_ascii_whitespace());
+
+        if self.index == start {
+            return None;
+        }
+
+        match self.peek(0) {
+            Some(char) if is_identifier_start(char) || char == b':' => {}
+            _ => return None,
+        }
+
+        let name_start = self.index;
+        self.skip_while(|char| is_identifier_char(char) || char == b':' || char == b'-');
+
+        Some(String::from_utf8_lossy(&self.contents[name_start..self.index]).to_string())
+    }
+
+    /// The name of a namespace declaration, empty for the global namespace and none for `namespace\foo()`
+    fn read_namespace_name(&mut self) -> Option<String> {
+        let start = self.index;
+        let mut name = String::new();
+
+        self.skip_while(|char| char.is_ascii_whitespace());
+        let separated = self.index > start;
+
+        if separated && self.peek(0).is_some_and(is_identifier_start) {
+            loop {
+                name.push_str(&String::from_utf8_lossy(self.read_identifier()));
+
+                let before_separator = self.index;
+                self.skip_while(|char| char.is_ascii_whitespace());
+                if self.peek(0) != Some(b'\\') {
+                    self.index = before_separator;
+                    break;
+                }
+
+                self.index += 1;
+                self.skip_while(|char| char.is_ascii_whitespace());
+                if !self.peek(0).is_some_and(is_identifier_start) {
+                    self.index = start;
+                    return None;
+                }
+                name.push('\\');
+            }
+        }
+
+        self.skip_while(|char| char.is_ascii_whitespace());
+        match self.peek(0) {
+            Some(b'{' | b';') => Some(name),
+            _ => {
+                self.index = start;
+                None
+            }
+        }
+    }
+}
+
+fn is_identifier_start(char: u8) -> bool {
+    char.is_ascii_alphabetic() || char == b'_' || char >= 0x80
+}
+
+fn is_identifier_char(char: u8) -> bool {
+    is_identifier_start(char) || char.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(contents: &str) -> Vec<String> {
+        find_classes(contents.as_bytes())
+    }
+
+    #[test]
+    fn finds_declarations() {
+        assert_eq!(
+            classes("<?php\nclass A {}\ninterface B {}\ntrait C {}\nenum D {}\nabstract class E {}\nfinal class F extends A implements B {}"),
+            vec!["A", "B", "C", "D", "E", "F"]
+        );
+    }
+
+    #[test]
+    fn applies_namespaces() {
+        assert_eq!(
+            classes("<?php\nnamespace Foo\\Bar;\nclass Baz {}\nnamespace Qux;\nnamespace\\helper();\ninterface Quux {}"),
+            vec!["Foo\\Bar\\Baz", "Qux\\Quux"]
+        );
+    }
+
+    #[test]
+    fn braced_global_namespace() {
+        assert_eq!(
+            classes(
+                "<?php\nnamespace Foo {\n    class Bar {}\n}\nnamespace {\n    class Baz {}\n}"
+            ),
+            vec!["Foo\\Bar", "Baz"]
+        );
+    }
+
+    #[test]
+    fn skips_strings() {
+        assert_eq!(
+            classes("<?php\n$a = 'class A {}';\n$b = \"class \\\"B\\\" {}\";\n$c = `class C`;\nclass D {}"),
+            vec!["D"]
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(
+            classes("<?php\n// class A {}\n# class B {}\n/* class C {} */\n/**\n * class D {}\n */\nclass E {}"),
+            vec!["E"]
+        );
+    }
+
+    #[test]
+    fn skips_heredocs_and_nowdocs() {
+        assert_eq!(
+            classes("<?php\n$a = <<<EOT\nclass A {}\nEOT;\n$b = <<<\"EOT\"\nclass B {}\nEOT;\n$c = <<<'EOT'\nclass C {}\nEOT;\nclass D {}"),
+            vec!["D"]
+        );
+    }
+
+    #[test]
+    fn nowdoc_with_indented_terminator() {
+        assert_eq!(
+            classes("<?php\n$a = <<<'EOT'\n    class A {}\n    EOTX\n    EOT;\nclass B {}"),
+            vec!["B"]
+        );
+    }
+
+    #[test]
+    fn skips_inline_html() {
+        assert_eq!(
+            classes("class A {}\n<?php class B {} ?>\nclass C {}\n<?php // class D ?>\nclass E {}\n<?PHP class F {}"),
+            vec!["B", "F"]
+        );
+    }
+
+    #[test]
+    fn skips_anonymous_classes_and_class_constants() {
+        assert_eq!(
+            classes("<?php\n$a = new class {};\n$b = new class extends Foo {};\n$c = Foo::class;\n$d = $e->class;\n$class = 1;\nclass A {}"),
+            vec!["A"]
+        );
+    }
+
+    #[test]
+    fn skips_strings_in_attributes() {
+        assert_eq!(classes("<?php\n#[Attr('class X')]\nclass A {}"), vec!["A"]);
+    }
+
+    #[test]
+    fn enum_backing_types() {
+        assert_eq!(
+            classes("<?php\nenum Suit: string {}\nenum Status:int {}"),
+            vec!["Suit", "Status"]
+        );
+    }
+
+    #[test]
+    fn xhp_class_names() {
+        assert_eq!(
+            classes("<?php\nclass :foo:bar-baz {}"),
+            vec!["xhp_foo__bar_baz"]
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Progress and warnings of an installation or autoloader generation, the library never prints
+/// them itself
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+    NothingToInstall,
+    Installing {
+        name: String,
+        version: String,
+    },
+    Updating {
+        name: String,
+        from: String,
+        to: String,
+    },
+    Removing {
+        name: String,
+        version: String,
+    },
+    /// A `bin` of a package is missing or its name is taken by the bin of another package
+    BinarySkipped {
+        package: String,
+        bin: String,
+        reason: String,
+    },
+    /// A cached archive is evicted and downloaded again
+    CachedArchiveMismatch {
+        url: String,
+    },
+    /// A class is declared in more than one file, the first one is used
+    AmbiguousClass {
+        class: String,
+        first: PathBuf,
+        other: PathBuf,
+    },
+    /// A class of a PSR-0 or PSR-4 directory is not where the standard expects it and is skipped
+    PsrViolation(String),
+    /// A PHP file could not be parsed, its classes are missing from the classmap
+    ScanFailed {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::NothingToInstall => write!(f, "Nothing to install, update or remove"),
+            Event::Installing { name, version } => {
+                write!(f, "Installing {} in version {}", name, version)
+            }
+            Event::Updating { name, from, to } => write!(f, "Updating {} ({} => {})", name, from, to),
+            Event::Removing { name, version } => write!(f, "Removing {} ({})", name, version),
+            Event::BinarySkipped {
+                package,
+                bin,
+                reason,
+            } => write!(
+                f,
+                "Skipped installation of bin {} for package {}: {}",
+                bin, package, reason
+            ),
+            Event::CachedArchiveMismatch { url } => write!(
+                f,
+                "Cached archive for {} does not match its checksum, downloading again",
+                url
+            ),
+            Event::AmbiguousClass {
+                class,
+                first,
+                other,
+            } => write!(
+                f,
+                "Warning: Ambiguous class resolution, \"{}\" was found in both \"{}\" and \"{}\", the first will be used.",
+                class,
+                first.display(),
+                other.display()
+            ),
+            Event::PsrViolation(message) => write!(f, "{}", message),
+            Event::ScanFailed { path, message } => {
+                write!(f, "Could not scan {} for classes: {}", path, message)
+            }
+        }
+    }
+}
+
+/// Receives the events of the library, by default they are dropped
+#[derive(Clone)]
+pub struct Reporter(Arc<dyn Fn(&Event) + Send + Sync>);
+
+impl Reporter {
+    pub fn new(report: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        Reporter(Arc::new(report))
+    }
+
+    pub(crate) fn report(&self, event: Event) {
+        (self.0)(&event)
+    }
+}
+
+impl Default for Reporter {
+    fn default() -> Self {
+        Reporter::new(|_| {})
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Reporter")
+    }
+}
@@ -0,0 +1,394 @@
+use std::cmp::Ordering;
+
+/// Normalizes a version the same way as Composer's VersionParser, e.g. `v1.2` becomes `1.2.0.0`
+pub fn normalize_version(version: &str) -> String {
+    let version = version.trim();
+
+    if version.to_lowercase().starts_with("dev-") {
+        return format!("dev-{}", &version[4..]);
+    }
+
+    // Build metadata is ignored by Composer, e.g. `1.0.0+no-version-set`
+    let version = version
+        .split_once('+')
+        .map_or(version, |(version, _)| version);
+    let lowercase = version.to_lowercase();
+
+    if let Some(branch) = lowercase
+        .strip_suffix("-dev")
+        .or_else(|| lowercase.strip_suffix(".dev"))
+    {
+        if let Some(normalized) = normalize_branch(branch) {
+            return normalized;
+        }
+    }
+
+    let without_prefix = lowercase.strip_prefix('v').unwrap_or(&lowercase);
+
+    normalize_numeric(without_prefix).unwrap_or_else(|| version.to_string())
+}
+
+/// Branch versions like `2.x-dev` are padded with 9999999 for every open segment
+fn normalize_branch(branch: &str) -> Option<String> {
+    let branch = branch.strip_prefix('v').unwrap_or(branch);
+    let segments = branch.split('.').collect::<Vec<&str>>();
+
+    if segments.is_empty() || segments.len() > 4 {
+        return None;
+    }
+
+    let mut normalized = vec![];
+    for (index, segment) in segments.iter().enumerate() {
+        match *segment {
+            "x" | "*" if index > 0 => normalized.push("9999999".to_string()),
+            segment if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) => {
+                normalized.push(segment.to_string())
+            }
+            _ => return None,
+        }
+    }
+
+    while normalized.len() < 4 {
+        normalized.push("9999999".to_string());
+    }
+
+    Some(format!("{}-dev", normalized.join(".")))
+}
+
+fn normalize_numeric(version: &str) -> Option<String> {
+    let numeric_end = version
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(version.len());
+    let (numbers, rest) = version.split_at(numeric_end);
+    let numbers = numbers.trim_end_matches('.');
+
+    let mut segments = numbers
+        .split('.')
+        .map(|segment| segment.parse::<u64>().ok().map(|n| n.to_string()))
+        .collect::<Option<Vec<String>>>()?;
+
+    if segments.is_empty() || segments.len() > 4 {
+        return None;
+    }
+
+    while segments.len() < 4 {
+        segments.push("0".to_string());
+    }
+
+    let mut normalized = segments.join(".");
+    let rest = rest.trim_start_matches(['.', '-', '_']);
+
+    if rest.is_empty() {
+        return Some(normalized);
+    }
+
+    let (modifier, dev) = match rest.strip_suffix("dev") {
+        Some(modifier) => (modifier.trim_end_matches(['.', '-']), true),
+        None => (rest, false),
+    };
+
+    if !modifier.is_empty() {
+        let stability_end = modifier
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(modifier.len());
+        let (stability, number) = modifier.split_at(stability_end);
+        let number = number.trim_start_matches(['.', '-']);
+
+        if !number.is_empty()
+            && !number
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == '-')
+        {
+            return None;
+        }
+
+        let stability = match stability {
+            "stable" => "",
+            "beta" | "b" => "beta",
+            "alpha" | "a" => "alpha",
+            "rc" => "RC",
+            "patch" | "pl" | "p" => "patch",
+            _ => return None,
+        };
+
+        if !stability.is_empty() {
+            normalized.push('-');
+            normalized.push_str(stability);
+            normalized.push_str(number);
+        }
+    }
+
+    if dev {
+        normalized.push_str("-dev");
+    }
+
+    Some(normalized)
+}
+
+/// The lowest version allowed by a constraint, like the lower bound of Composer's constraints
+#[derive(Clone, PartialEq)]
+pub struct Bound {
+    pub version: String,
+    pub inclusive: bool,
+}
+
+impl Bound {
+    pub fn zero() -> Self {
+        Bound {
+            version: "0.0.0.0-dev".to_string(),
+            inclusive: true,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Bound::zero()
+    }
+
+    /// An exclusive bound is higher than an inclusive bound of the same version
+    pub fn is_higher_than(&self, other: &Bound) -> bool {
+        if self == other {
+            return false;
+        }
+
+        match compare_versions(&self.version, &other.version) {
+            Ordering::Equal => other.inclusive,
+            ordering => ordering == Ordering::Greater,
+        }
+    }
+
+    /// The comparison operator checking a version against the bound
+    pub fn operator(&self) -> &'static str {
+        match self.inclusive {
+            true => ">=",
+            false => ">",
+        }
+    }
+
+    /// The bound as PHP's `PHP_VERSION_ID`, e.g. `70203` for `7.2.3`
+    pub fn php_version_id(&self) -> u64 {
+        let segments = self.segments();
+        let number = |index: usize| {
+            segments
+                .get(index)
+                .and_then(|segment| segment.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+
+        number(0) * 10000 + number(1) * 100 + number(2)
+    }
+
+    /// The first three segments of the version as shown to users, e.g. `7.2.3`
+    pub fn php_version(&self) -> String {
+        let segments = self.segments();
+        segments[..segments.len().min(3)].join(".")
+    }
+
+    fn segments(&self) -> Vec<&str> {
+        self.version.split(['.', '-']).collect()
+    }
+}
+
+/// The lower bound of a constraint like `^7.2 || >=8.0`, alternatives take the lowest bound of
+/// their constraints and constraints which are combined the highest
+pub fn constraint_lower_bound(constraint: &str) -> Bound {
+    let mut lowest: Option<Bound> = None;
+
+    for alternative in constraint.split('|').map(str::trim) {
+        if alternative.is_empty() {
+            continue;
+        }
+
+        let mut highest = Bound::zero();
+        for single in split_constraints(alternative) {
+            let bound = single_lower_bound(&single);
+            if bound.is_higher_than(&highest) {
+                highest = bound;
+            }
+        }
+
+        if lowest
+            .as_ref()
+            .is_none_or(|lowest| lowest.is_higher_than(&highest))
+        {
+            lowest = Some(highest);
+        }
+    }
+
+    lowest.unwrap_or_else(Bound::zero)
+}
+
+/// Splits combined constraints on commas and spaces, keeping operators with their version,
+/// e.g. `>= 7.1, <8` and hyphen ranges like `7.1 - 7.4`
+fn split_constraints(constraint: &str) -> Vec<String> {
+    let mut constraints = vec![];
+    let mut tokens = constraint
+        .split(|char: char| char == ',' || char.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .peekable();
+
+    while let Some(token) = tokens.next() {
+        // Only the lower end of a hyphen range is a bound
+        if tokens.peek() == Some(&"-") {
+            tokens.next();
+            tokens.next();
+            constraints.push(format!(">={}", token));
+            continue;
+        }
+
+        if token.chars().all(is_operator_char) {
+            if let Some(version) = tokens.next() {
+                constraints.push(format!("{}{}", token, version));
+            }
+            continue;
+        }
+
+        constraints.push(token.to_string());
+    }
+
+    constraints
+}
+
+fn single_lower_bound(constraint: &str) -> Bound {
+    // Stability flags like `@dev` do not change the bound
+    let constraint = constraint.split('@').next().unwrap_or_default();
+    let operator_end = constraint
+        .find(|char: char| !is_operator_char(char))
+        .unwrap_or(constraint.len());
+    let (operator, version) = constraint.split_at(operator_end);
+
+    // Wildcards like `7.2.*` start at the lowest version of the wildcard
+    let segments = version.split('.').collect::<Vec<&str>>();
+    if let Some(position) = segments
+        .iter()
+        .position(|segment| matches!(*segment, "*" | "x" | "X"))
+    {
+        return match position {
+            0 => Bound::zero(),
+            _ => Bound {
+                version: format!("{}-dev", normalize_version(&segments[..position].join("."))),
+                inclusive: true,
+            },
+        };
+    }
+
+    let normalized = normalize_version(version);
+
+    // Branches like `dev-main` allow any version
+    if !normalized.starts_with(|char: char| char.is_ascii_digit()) {
+        return Bound::zero();
+    }
+
+    match operator {
+        "<" | "<=" | "!=" | "<>" => Bound::zero(),
+        ">" => Bound {
+            version: normalized,
+            inclusive: false,
+        },
+        // Like Composer these include the pre-releases of their version unless one is given
+        "^" | "~" | ">=" if !normalized.contains('-') => Bound {
+            version: format!("{}-dev", normalized),
+            inclusive: true,
+        },
+        _ => Bound {
+            version: normalized,
+            inclusive: true,
+        },
+    }
+}
+
+fn is_operator_char(char: char) -> bool {
+    matches!(char, '<' | '>' | '=' | '!' | '^' | '~')
+}
+
+/// Compares normalized versions like PHP's version_compare, `-dev` comes before alpha, beta and RC
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |version: &str| {
+        let (numbers, stability) = version.split_once('-').unwrap_or((version, ""));
+        let mut numbers = numbers
+            .split('.')
+            .map(|number| number.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<u64>>();
+        numbers.resize(4, 0);
+
+        let stability = stability.to_lowercase();
+        let rank = match stability.as_str() {
+            stability if stability.starts_with("dev") => 0,
+            stability if stability.starts_with("alpha") => 1,
+            stability if stability.starts_with("beta") => 2,
+            stability if stability.starts_with("rc") => 3,
+            "" => 4,
+            _ => 5,
+        };
+        let number = stability
+            .trim_start_matches(|char: char| !char.is_ascii_digit())
+            .parse::<u64>()
+            .unwrap_or(0);
+
+        (numbers, rank, number)
+    };
+
+    parse(a).cmp(&parse(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_versions() {
+        for (version, normalized) in [
+            ("1.0", "1.0.0.0"),
+            ("v2.3.4", "2.3.4.0"),
+            ("1.0.0-RC1", "1.0.0.0-RC1"),
+            ("1.0.0-beta.2", "1.0.0.0-beta2"),
+            ("2.x-dev", "2.9999999.9999999.9999999-dev"),
+            ("dev-main", "dev-main"),
+            ("1.0.0+no-version-set", "1.0.0.0"),
+        ] {
+            assert_eq!(normalize_version(version), normalized, "{}", version);
+        }
+    }
+
+    #[test]
+    fn constraint_lower_bounds() {
+        for (constraint, version, inclusive, version_id, php_version) in [
+            ("^7.2 || ^8.0", "7.2.0.0-dev", true, 70200, "7.2.0"),
+            (">=7.4 <8.0", "7.4.0.0-dev", true, 70400, "7.4.0"),
+            (">= 7.1, <8", "7.1.0.0-dev", true, 70100, "7.1.0"),
+            ("7.1 - 7.4", "7.1.0.0-dev", true, 70100, "7.1.0"),
+            (">7.4", "7.4.0.0", false, 70400, "7.4.0"),
+            ("8.1.*", "8.1.0.0-dev", true, 80100, "8.1.0"),
+            ("~7.1.3", "7.1.3.0-dev", true, 70103, "7.1.3"),
+            ("dev-main", "0.0.0.0-dev", true, 0, "0.0.0"),
+            ("1.0.0-RC1", "1.0.0.0-RC1", true, 10000, "1.0.0"),
+            ("<8.0 || >=8.1", "0.0.0.0-dev", true, 0, "0.0.0"),
+        ] {
+            let bound = constraint_lower_bound(constraint);
+            assert_eq!(bound.version, version, "{}", constraint);
+            assert_eq!(bound.inclusive, inclusive, "{}", constraint);
+            assert_eq!(bound.php_version_id(), version_id, "{}", constraint);
+            assert_eq!(bound.php_version(), php_version, "{}", constraint);
+        }
+    }
+
+    #[test]
+    fn exclusive_bounds_are_higher() {
+        let inclusive = constraint_lower_bound(">=7.4.0");
+        let exclusive = constraint_lower_bound(">7.4.0");
+        assert!(exclusive.is_higher_than(&inclusive));
+        assert!(!inclusive.is_higher_than(&exclusive));
+        assert_eq!(exclusive.operator(), ">");
+        assert_eq!(inclusive.operator(), ">=");
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(
+            compare_versions("1.0.0.0-dev", "1.0.0.0-alpha1"),
+            Ordering::Less
+        );
+        assert_eq!(compare_versions("1.0.0.0-RC1", "1.0.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.10.0.0", "1.9.0.0"), Ordering::Greater);
+        assert_eq!(compare_versions("7.4.0.0", "7.4"), Ordering::Equal);
+    }
+}